@@ -39,6 +39,46 @@ pub fn init_map(position_component: Component<IVec2>) -> PositionMap {
     chunks
 }
 
+/// Spawns an instance of an item class using the items package's class,
+/// quantity, and durability components. Durability of zero means none.
+pub fn spawn_instance(
+    class_component: Component<EntityId>,
+    quantity_component: Component<u32>,
+    durability_component: Component<u32>,
+    item_class: EntityId,
+    count: u32,
+    tool_durability: u32,
+) -> EntityId {
+    let mut instance = Entity::new()
+        .with(class_component, item_class)
+        .with(quantity_component, count);
+
+    if tool_durability > 0 {
+        instance.set(durability_component, tool_durability);
+    }
+
+    instance.spawn()
+}
+
+/// Tests if a container has room for another item of a class using the items
+/// package's container_classes, container_counts, and max_stack components.
+pub fn has_room_for(
+    classes_component: Component<Vec<EntityId>>,
+    counts_component: Component<Vec<u32>>,
+    max_stack_component: Component<u32>,
+    container: EntityId,
+    item_class: EntityId,
+) -> bool {
+    let classes = entity::get_component(container, classes_component).unwrap_or_default();
+    let counts = entity::get_component(container, counts_component).unwrap_or_default();
+    let max = entity::get_component(item_class, max_stack_component).unwrap_or(1);
+
+    classes
+        .into_iter()
+        .zip(counts)
+        .any(|(slot, count)| slot.is_null() || (slot == item_class && count < max))
+}
+
 /// A utility function to diff two sorted iterators.
 pub fn diff_sorted<'a, V>(
     mut a_iter: impl Iterator<Item = V>,
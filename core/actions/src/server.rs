@@ -3,13 +3,15 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-use ambient_api::prelude::*;
+use ambient_api::{core::player::components::user_id, prelude::*};
 
-use flowerpot_common::ActorExt;
+use flowerpot_common::{ActorExt, CHUNK_SIZE};
 use packages::{
-    items::components::held_ref,
-    map::components::{chunk, chunk_tile_refs},
-    player::components::{left_hand_ref, right_hand_ref},
+    crops::components::{large_crop_occupant, medium_crop_occupant, small_crop_occupant},
+    items::components::{class, held_ref},
+    map::components::{chunk, chunk_tile_refs, position},
+    player::components::{left_hand_ref, reach, right_hand_ref},
+    storage::components::chest_occupant,
     things::components::class_ref,
     this::messages::*,
 };
@@ -18,7 +20,10 @@ mod shared;
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum ActionTarget {
+    SmallCrop(EntityId),
     MediumCrop(EntityId),
+    LargeCrop(EntityId),
+    Chest(EntityId),
     Tile,
     Crafting,
 }
@@ -57,8 +62,8 @@ impl ActionContext {
         let left_hand = entity::get_component(player, left_hand_ref())?;
         let right_hand = entity::get_component(player, right_hand_ref())?;
 
-        let left_held = entity::get_component(left_hand, held_ref()).unwrap_or_default();
-        let right_held = entity::get_component(right_hand, held_ref()).unwrap_or_default();
+        let left_held = get_held_class(left_hand);
+        let right_held = get_held_class(right_hand);
 
         let (both, right_is_primary) = Self::new(left_held, right_held);
         if let Some(result) = cb(both, right_is_primary) {
@@ -83,6 +88,12 @@ impl ActionContext {
     }
 }
 
+/// Gets the class of the item held by a hand, or null if it's empty.
+pub fn get_held_class(hand: EntityId) -> EntityId {
+    let held = entity::get_component(hand, held_ref()).unwrap_or_default();
+    entity::get_component(held, class()).unwrap_or_default()
+}
+
 #[derive(Clone, Debug)]
 pub struct ActionCallback {
     pub module: EntityId,
@@ -95,6 +106,9 @@ pub type ActionStore = HashMap<ActionContext, ActionCallback>;
 #[derive(Clone, Default)]
 pub struct ActionRegistry {
     pub targets: HashMap<ActionTarget, ActionStore>,
+
+    /// Actions performed when no context of their target matches.
+    pub fallbacks: HashMap<ActionTarget, ActionCallback>,
 }
 
 impl ActionRegistry {
@@ -118,16 +132,54 @@ impl ActionRegistry {
         store.insert(context, cb);
     }
 
+    pub fn register_fallback(&mut self, target: ActionTarget, cb: ActionCallback) {
+        eprintln!("registering {:?} fallback action: {:?}", target, cb);
+
+        if let Some(old) = self.fallbacks.insert(target, cb) {
+            eprintln!("replaced {:?} fallback action: {:?}", target, old);
+        }
+    }
+
     pub fn perform_action(
         &self,
         target: ActionTarget,
         player: EntityId,
     ) -> Option<(ActionCallback, bool)> {
-        let store = self.targets.get(&target)?;
+        let action = self.targets.get(&target).and_then(|store| {
+            ActionContext::for_player_contexts(player, move |context, right_is_primary| {
+                store.get(&context).map(|cb| (cb.clone(), right_is_primary))
+            })
+        });
 
-        ActionContext::for_player_contexts(player, move |context, right_is_primary| {
-            store.get(&context).map(|cb| (cb.clone(), right_is_primary))
-        })
+        action.or_else(|| self.fallbacks.get(&target).map(|cb| (cb.clone(), false)))
+    }
+}
+
+/// Tests if a tile is within a player's reach.
+pub fn is_in_reach(player: EntityId, chunk_pos: IVec2, tile_idx: u8) -> bool {
+    let Some(position) = entity::get_component(player, position()) else {
+        return false;
+    };
+
+    let Some(reach) = entity::get_component(player, reach()) else {
+        return false;
+    };
+
+    let local = ivec2(
+        tile_idx as i32 % CHUNK_SIZE as i32,
+        tile_idx as i32 / CHUNK_SIZE as i32,
+    );
+
+    // measure to the nearest point on the tile, since that's what players aim at
+    let tile_min = (chunk_pos * CHUNK_SIZE as i32 + local).as_vec2();
+    let nearest = position.clamp(tile_min, tile_min + Vec2::ONE);
+    nearest.distance(position) <= reach
+}
+
+/// Tells a player's client that their action was rejected.
+pub fn reject_action(player: EntityId, reason: &str) {
+    if let Some(uid) = entity::get_component(player, user_id()) {
+        ActionRejected::new(reason.to_string()).send_client_targeted_reliable(uid);
     }
 }
 
@@ -142,7 +194,12 @@ fn main() {
         let id = data.id;
         let cb = ActionCallback { module, id };
 
-        registry.register_action(ActionTarget::Crafting, context, cb);
+        // crafting without any held items matches whatever players are holding
+        if context.primary_held.is_null() && context.secondary_held.is_null() {
+            registry.register_fallback(ActionTarget::Crafting, cb);
+        } else {
+            registry.register_action(ActionTarget::Crafting, context, cb);
+        }
     });
 
     registry.on_local_message(move |registry, module, data: RegisterMediumCropAction| {
@@ -155,6 +212,36 @@ fn main() {
         registry.register_action(ActionTarget::MediumCrop(data.class), context, cb);
     });
 
+    registry.on_local_message(move |registry, module, data: RegisterSmallCropAction| {
+        let (context, _right_is_primary) =
+            ActionContext::new(data.primary_held, data.secondary_held);
+
+        let id = data.id;
+        let cb = ActionCallback { module, id };
+
+        registry.register_action(ActionTarget::SmallCrop(data.class), context, cb);
+    });
+
+    registry.on_local_message(move |registry, module, data: RegisterLargeCropAction| {
+        let (context, _right_is_primary) =
+            ActionContext::new(data.primary_held, data.secondary_held);
+
+        let id = data.id;
+        let cb = ActionCallback { module, id };
+
+        registry.register_action(ActionTarget::LargeCrop(data.class), context, cb);
+    });
+
+    registry.on_local_message(move |registry, module, data: RegisterChestAction| {
+        let (context, _right_is_primary) =
+            ActionContext::new(data.primary_held, data.secondary_held);
+
+        let id = data.id;
+        let cb = ActionCallback { module, id };
+
+        registry.register_action(ActionTarget::Chest(data.class), context, cb);
+    });
+
     registry.on_local_message(move |registry, module, data: RegisterTileAction| {
         let (context, _right_is_primary) =
             ActionContext::new(data.primary_held, data.secondary_held);
@@ -166,7 +253,18 @@ fn main() {
     });
 
     registry.on_client_message(move |registry, player, _data: PerformCraftingAction| {
-        registry.perform_action(ActionTarget::Crafting, player);
+        // the crafting module responds once it has applied or rejected the recipe
+        let Some((cb, right_is_primary)) = registry.perform_action(ActionTarget::Crafting, player)
+        else {
+            if let Some(uid) = entity::get_component(player, user_id()) {
+                let reason = "Nothing to craft with these items.".to_string();
+                CraftingResult::new(false, reason).send_client_targeted_reliable(uid);
+            }
+
+            return;
+        };
+
+        OnAction::new(cb.id, player, right_is_primary, EntityId::null()).send_local(cb.module);
     });
 
     let chunks = flowerpot_common::init_map(chunk());
@@ -175,6 +273,7 @@ fn main() {
 
         let Some(chunk) = chunks.get(&data.chunk_pos) else {
             eprintln!("tile action on chunk {} is OOB", data.chunk_pos);
+            reject_action(player, "That tile isn't loaded.");
             return;
         };
 
@@ -187,23 +286,42 @@ fn main() {
             return;
         };
 
-        if data.on_occupant {
-            use crate::packages::crops::components::*;
-            let Some(occupant) = entity::get_component(*tile, medium_crop_occupant()) else {
-                return;
-            };
-
-            let Some(class) = entity::get_component(occupant, class_ref()) else {
-                return;
-            };
+        if !is_in_reach(player, data.chunk_pos, data.tile_idx) {
+            reject_action(player, "That tile is out of reach.");
+            return;
+        }
 
-            let Some((cb, right_is_primary)) = registry.perform_action(ActionTarget::Tile, player)
-            else {
-                return;
-            };
+        // route actions on occupants by the occupant's class, falling back on
+        // the tile's own actions if no occupant has one for this context.
+        // occupants are tried from the top down, so a small crop underneath a
+        // medium crop is targeted only if the medium crop has no action.
+        let layers: [(Component<EntityId>, fn(EntityId) -> ActionTarget); 4] = [
+            (chest_occupant(), ActionTarget::Chest),
+            (large_crop_occupant(), ActionTarget::LargeCrop),
+            (medium_crop_occupant(), ActionTarget::MediumCrop),
+            (small_crop_occupant(), ActionTarget::SmallCrop),
+        ];
+
+        let on_occupant = layers
+            .into_iter()
+            .filter(|_| data.on_occupant)
+            .find_map(|(occupant, target)| {
+                let occupant = entity::get_component(*tile, occupant)?;
+                let class = entity::get_component(occupant, class_ref())?;
+                registry
+                    .perform_action(target(class), player)
+                    .map(|action| (action, occupant))
+            });
+
+        let Some(((cb, right_is_primary), target)) = on_occupant.or_else(|| {
+            registry
+                .perform_action(ActionTarget::Tile, player)
+                .map(|action| (action, *tile))
+        }) else {
+            return;
+        };
 
-            OnAction::new(cb.id, player, right_is_primary, *tile).send_local(cb.module);
-        }
+        OnAction::new(cb.id, player, right_is_primary, target).send_local(cb.module);
     });
 
     PerformSwap::subscribe(move |source, _data| {
@@ -0,0 +1,6 @@
+use ambient_api::prelude::*;
+
+mod shared;
+
+#[main]
+fn main() {}
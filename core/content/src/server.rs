@@ -0,0 +1,450 @@
+use std::collections::{BTreeMap, HashMap};
+
+use ambient_api::{core::rendering::components::color, prelude::*};
+use serde::Deserialize;
+
+use packages::{
+    crafting::components::*,
+    crops::components::{
+        allowed_seasons, comfortable_temperature, companion_tags, crop_tags, fertility_use,
+        inhibitor_tags, is_cover_crop, is_large_crop, is_medium_crop, is_small_crop,
+        large_crop_footprint, max_spread_density, model_variants, next_growth_age,
+        next_growth_stage, pollination_tag, pollination_threshold, seed, seeding_interval,
+        survivable_temperature,
+    },
+    game::components::{
+        pick_up_item_class, pick_up_next_stage, place_chest, place_large_crop,
+        place_medium_crop, place_small_crop, tills_soil, waters_soil,
+    },
+    items::components::{is_item, is_tool, item_tags, max_durability, max_stack},
+    map::components::speed_multiplier,
+    nameplate::components::name,
+    storage::components::{chest_size, is_chest},
+    things::components::{class_id, is_class, model_prefab_url},
+    this::messages::*,
+};
+
+mod shared;
+
+/// The contents of a single content file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ContentFile {
+    /// Class definitions by class ID.
+    #[serde(default)]
+    pub classes: BTreeMap<String, ClassDef>,
+
+    #[serde(default)]
+    pub recipes: Vec<RecipeDef>,
+}
+
+/// A crop or item class. All fields referring to other classes take class IDs.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClassDef {
+    pub name: Option<String>,
+
+    /// The path to the class's model, relative to the content file.
+    pub model: Option<String>,
+    /// Paths to alternative models, one of which is picked per instance.
+    #[serde(default)]
+    pub model_variants: Vec<String>,
+    pub color: Option<[f32; 4]>,
+    pub speed_multiplier: Option<f32>,
+
+    #[serde(default)]
+    pub small_crop: bool,
+    #[serde(default)]
+    pub cover_crop: bool,
+    pub max_spread_density: Option<f32>,
+    #[serde(default)]
+    pub medium_crop: bool,
+    #[serde(default)]
+    pub large_crop: bool,
+    pub footprint: Option<[u32; 2]>,
+    pub next_stage: Option<String>,
+    pub next_growth_age: Option<u16>,
+    pub seed: Option<String>,
+    pub seeding_interval: Option<u16>,
+    pub pick_up_item: Option<String>,
+    pub pick_up_next_stage: Option<String>,
+
+    /// Tags describing this crop to its neighbors.
+    #[serde(default)]
+    pub crop_tags: Vec<String>,
+    pub pollination_tag: Option<String>,
+    pub pollination_threshold: Option<u8>,
+    #[serde(default)]
+    pub inhibitor_tags: Vec<String>,
+    #[serde(default)]
+    pub companion_tags: Vec<String>,
+    pub fertility_use: Option<f32>,
+    /// The seasons (0 spring, 1 summer, 2 autumn, 3 winter) this crop grows in.
+    #[serde(default)]
+    pub allowed_seasons: Vec<u8>,
+    pub comfortable_temperature: Option<[f32; 2]>,
+    pub survivable_temperature: Option<[f32; 2]>,
+
+    #[serde(default)]
+    pub item: bool,
+    #[serde(default)]
+    pub tool: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub max_stack: Option<u32>,
+    /// How many uses new instances of this tool have before they break.
+    pub max_durability: Option<u32>,
+    pub place_small_crop: Option<String>,
+    pub place_medium_crop: Option<String>,
+    pub place_large_crop: Option<String>,
+    #[serde(default)]
+    pub tills_soil: bool,
+    /// How much moisture this item adds to the soil of the tiles it waters.
+    pub waters_soil: Option<f32>,
+    pub place_chest: Option<String>,
+
+    #[serde(default)]
+    pub chest: bool,
+    /// The number of slots in chests of this class.
+    pub chest_size: Option<u32>,
+}
+
+/// A crafting recipe. See crafting::is_recipe for how ingredients are matched.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RecipeDef {
+    pub primary: Option<String>,
+    pub primary_tag: Option<String>,
+    pub secondary: Option<String>,
+    pub secondary_tag: Option<String>,
+    #[serde(default)]
+    pub any_secondary: bool,
+    #[serde(default)]
+    pub yields: Vec<YieldDef>,
+}
+
+/// One of a recipe's possible outcomes.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct YieldDef {
+    pub primary: Option<String>,
+    pub secondary: Option<String>,
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+/// Resolves class IDs into class entities while loading a content file.
+pub struct ContentLoader {
+    base_url: String,
+    classes: HashMap<String, EntityId>,
+    errors: usize,
+}
+
+impl ContentLoader {
+    pub fn new(url: &str) -> Self {
+        let base_url = match url.rsplit_once('/') {
+            Some((base, _file)) => format!("{}/", base),
+            None => String::new(),
+        };
+
+        // classes that already exist may be referenced or extended
+        let classes = query(class_id())
+            .requires(is_class())
+            .build()
+            .evaluate()
+            .into_iter()
+            .map(|(e, id)| (id, e))
+            .collect();
+
+        Self {
+            base_url,
+            classes,
+            errors: 0,
+        }
+    }
+
+    /// Looks up a class by ID, logging an error if it doesn't exist.
+    pub fn resolve(&mut self, id: &str) -> EntityId {
+        match self.classes.get(id) {
+            Some(e) => *e,
+            None => {
+                eprintln!("unknown class ID {:?}", id);
+                self.errors += 1;
+                EntityId::null()
+            }
+        }
+    }
+
+    pub fn resolve_opt(&mut self, id: &Option<String>) -> Option<EntityId> {
+        id.as_ref().map(|id| self.resolve(id))
+    }
+
+    pub fn load(&mut self, content: ContentFile) {
+        // spawn every class before resolving any references so that classes
+        // may refer to each other in any order
+        for id in content.classes.keys() {
+            if !self.classes.contains_key(id) {
+                let e = Entity::new().spawn();
+                self.classes.insert(id.clone(), e);
+            }
+        }
+
+        for (id, def) in content.classes.iter() {
+            let e = self.classes[id];
+            let class = self.build_class(id, def);
+            entity::add_components(e, class);
+        }
+
+        for recipe in content.recipes.iter() {
+            if let Some(recipe) = self.build_recipe(recipe) {
+                recipe.spawn();
+            }
+        }
+    }
+
+    fn build_class(&mut self, id: &str, def: &ClassDef) -> Entity {
+        let mut class = Entity::new()
+            .with(is_class(), ())
+            .with(class_id(), id.to_string());
+
+        if let Some(label) = def.name.as_ref() {
+            class.set(name(), label.clone());
+        }
+
+        if let Some(model) = def.model.as_ref() {
+            class.set(model_prefab_url(), format!("{}{}", self.base_url, model));
+        }
+
+        if !def.model_variants.is_empty() {
+            let variants = def
+                .model_variants
+                .iter()
+                .map(|model| format!("{}{}", self.base_url, model))
+                .collect();
+
+            class.set(model_variants(), variants);
+        }
+
+        if let Some(rgba) = def.color {
+            class.set(color(), Vec4::from_array(rgba));
+        }
+
+        if let Some(multiplier) = def.speed_multiplier {
+            class.set(speed_multiplier(), multiplier);
+        }
+
+        if def.small_crop {
+            class.set(is_small_crop(), ());
+        }
+
+        if def.cover_crop {
+            class.set(is_cover_crop(), ());
+        }
+
+        if let Some(density) = def.max_spread_density {
+            class.set(max_spread_density(), density);
+        }
+
+        if def.medium_crop {
+            class.set(is_medium_crop(), ());
+        }
+
+        if def.large_crop {
+            class.set(is_large_crop(), ());
+        }
+
+        if let Some(footprint) = def.footprint {
+            class.set(large_crop_footprint(), UVec2::from_array(footprint));
+        }
+
+        if let Some(age) = def.next_growth_age {
+            let next = self.resolve_opt(&def.next_stage).unwrap_or_default();
+            class.set(next_growth_age(), age);
+            class.set(next_growth_stage(), next);
+        }
+
+        if let Some(interval) = def.seeding_interval {
+            match self.resolve_opt(&def.seed) {
+                Some(seed_class) => {
+                    class.set(seeding_interval(), interval);
+                    class.set(seed(), seed_class);
+                }
+                None => {
+                    eprintln!("class {:?} has a seeding interval but no seed", id);
+                    self.errors += 1;
+                }
+            }
+        }
+
+        if !def.crop_tags.is_empty() {
+            class.set(crop_tags(), def.crop_tags.clone());
+        }
+
+        if let Some(tag) = def.pollination_tag.as_ref() {
+            class.set(pollination_tag(), tag.clone());
+            class.set(pollination_threshold(), def.pollination_threshold.unwrap_or(1));
+        }
+
+        if !def.inhibitor_tags.is_empty() {
+            class.set(inhibitor_tags(), def.inhibitor_tags.clone());
+        }
+
+        if !def.companion_tags.is_empty() {
+            class.set(companion_tags(), def.companion_tags.clone());
+        }
+
+        if let Some(used) = def.fertility_use {
+            class.set(fertility_use(), used);
+        }
+
+        if !def.allowed_seasons.is_empty() {
+            class.set(allowed_seasons(), def.allowed_seasons.clone());
+        }
+
+        if let Some(range) = def.comfortable_temperature {
+            class.set(comfortable_temperature(), Vec2::from_array(range));
+        }
+
+        if let Some(range) = def.survivable_temperature {
+            class.set(survivable_temperature(), Vec2::from_array(range));
+        }
+
+        if let Some(item) = self.resolve_opt(&def.pick_up_item) {
+            class.set(pick_up_item_class(), item);
+        }
+
+        if let Some(next) = self.resolve_opt(&def.pick_up_next_stage) {
+            class.set(pick_up_next_stage(), next);
+        }
+
+        if def.item {
+            class.set(is_item(), ());
+            class.set(item_tags(), def.tags.clone());
+        }
+
+        if def.tool {
+            class.set(is_tool(), ());
+        }
+
+        if let Some(max) = def.max_stack {
+            class.set(max_stack(), max);
+        }
+
+        if let Some(uses) = def.max_durability {
+            class.set(max_durability(), uses);
+        }
+
+        if let Some(crop) = self.resolve_opt(&def.place_small_crop) {
+            class.set(place_small_crop(), crop);
+        }
+
+        if let Some(crop) = self.resolve_opt(&def.place_medium_crop) {
+            class.set(place_medium_crop(), crop);
+        }
+
+        if let Some(crop) = self.resolve_opt(&def.place_large_crop) {
+            class.set(place_large_crop(), crop);
+        }
+
+        if def.tills_soil {
+            class.set(tills_soil(), ());
+        }
+
+        if let Some(amount) = def.waters_soil {
+            class.set(waters_soil(), amount);
+        }
+
+        if let Some(chest) = self.resolve_opt(&def.place_chest) {
+            class.set(place_chest(), chest);
+        }
+
+        if def.chest {
+            class.set(is_chest(), ());
+        }
+
+        if let Some(size) = def.chest_size {
+            class.set(chest_size(), size);
+        }
+
+        class
+    }
+
+    fn build_recipe(&mut self, def: &RecipeDef) -> Option<Entity> {
+        let mut recipe = Entity::new().with(is_recipe(), ());
+
+        if let Some(primary) = self.resolve_opt(&def.primary) {
+            recipe.set(primary_ingredient(), primary);
+        } else if let Some(tag) = def.primary_tag.as_ref() {
+            recipe.set(primary_ingredient_tag(), tag.clone());
+        } else {
+            eprintln!("recipe has no primary ingredient: {:?}", def);
+            self.errors += 1;
+            return None;
+        }
+
+        if let Some(secondary) = self.resolve_opt(&def.secondary) {
+            recipe.set(secondary_ingredient(), secondary);
+        } else if let Some(tag) = def.secondary_tag.as_ref() {
+            recipe.set(secondary_ingredient_tag(), tag.clone());
+        } else if def.any_secondary {
+            recipe.set(any_secondary_ingredient(), ());
+        }
+
+        let mut primaries = Vec::with_capacity(def.yields.len());
+        let mut secondaries = Vec::with_capacity(def.yields.len());
+        let mut weights = Vec::with_capacity(def.yields.len());
+        for outcome in def.yields.iter() {
+            primaries.push(self.resolve_opt(&outcome.primary).unwrap_or_default());
+            secondaries.push(self.resolve_opt(&outcome.secondary).unwrap_or_default());
+            weights.push(outcome.weight);
+        }
+
+        recipe.set(primary_yields(), primaries);
+        recipe.set(secondary_yields(), secondaries);
+        recipe.set(yield_weights(), weights);
+
+        Some(recipe)
+    }
+}
+
+async fn load_content(url: &str) -> Result<(), String> {
+    let bytes = http::get(url)
+        .await
+        .map_err(|err| format!("failed to fetch: {}", err))?;
+
+    let text = String::from_utf8(bytes).map_err(|err| format!("invalid UTF-8: {}", err))?;
+
+    let content: ContentFile =
+        toml::from_str(&text).map_err(|err| format!("failed to parse: {}", err))?;
+
+    let mut loader = ContentLoader::new(url);
+    loader.load(content);
+
+    match loader.errors {
+        0 => Ok(()),
+        errors => Err(format!("{} errors while resolving references", errors)),
+    }
+}
+
+#[main]
+fn main() {
+    LoadContent::subscribe(move |source, data| {
+        if source.local().is_none() {
+            return;
+        }
+
+        run_async(async move {
+            let result = load_content(&data.url).await;
+
+            if let Err(err) = result.as_ref() {
+                eprintln!("error loading content from {}: {}", data.url, err);
+            }
+
+            OnContentLoaded::new(data.url, result.is_ok()).send_local_broadcast(true);
+        });
+    });
+}
@@ -1,83 +1,338 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     sync::{Arc, Mutex},
 };
 
-use ambient_api::prelude::*;
+use ambient_api::{core::player::components::user_id, prelude::*};
 
+use flowerpot_common::{has_room_for, spawn_instance, ActorExt, SystemExt};
 use packages::{
     actions::messages::*,
-    items::components::held_ref,
+    items::{
+        components::{
+            class, container_classes, container_counts, durability, held_ref, inventory_ref,
+            is_item, is_tool, item_tags, max_durability, max_stack, quantity,
+        },
+        messages::StoreItems,
+    },
     player::components::{left_hand_ref, right_hand_ref},
     this::components::*,
 };
 
 mod shared;
 
-pub struct CraftingYield {
+/// Matches the items that a recipe can use as an ingredient.
+#[derive(Clone, Debug)]
+pub enum IngredientPattern {
+    /// Matches a single item class, or an empty hand if null.
+    Class(EntityId),
+
+    /// Matches any item class with this tag.
+    Tag(String),
+
+    /// Matches any item class or an empty hand.
+    Any,
+}
+
+impl IngredientPattern {
+    pub fn matches(&self, class: EntityId, item: Option<&ItemInfo>) -> bool {
+        match self {
+            IngredientPattern::Class(expected) => *expected == class,
+            IngredientPattern::Tag(tag) => item.map(|item| item.tags.contains(tag)) == Some(true),
+            IngredientPattern::Any => true,
+        }
+    }
+
+    /// How specific this pattern is. More specific recipes take precedence.
+    pub fn specificity(&self) -> u8 {
+        match self {
+            IngredientPattern::Class(_) => 2,
+            IngredientPattern::Tag(_) => 1,
+            IngredientPattern::Any => 0,
+        }
+    }
+}
+
+pub struct CraftingOutcome {
     pub primary: EntityId,
     pub secondary: EntityId,
-    pub write_secondary: bool,
+    pub weight: f32,
 }
 
-#[main]
-fn main() {
-    let store = Arc::new(Mutex::new(HashMap::<String, CraftingYield>::new()));
-
-    spawn_query((
-        is_recipe(),
-        primary_ingredient(),
-        secondary_ingredient(),
-        primary_yield(),
-        secondary_yield(),
-    ))
-    .bind({
-        let store = store.clone();
-        move |entities| {
-            let mut store = store.lock().unwrap();
-            for (
-                e,
-                (_recipe, primary_ingredient, secondary_ingredient, primary_yield, secondary_yield),
-            ) in entities
-            {
-                let id = e.to_string();
-                let crafting_yield = CraftingYield {
-                    primary: primary_yield,
-                    secondary: secondary_yield,
-                    write_secondary: secondary_yield != secondary_ingredient,
-                };
+pub struct Recipe {
+    pub primary: IngredientPattern,
+    pub secondary: IngredientPattern,
+    pub outcomes: Vec<CraftingOutcome>,
+}
+
+impl Recipe {
+    pub fn get(e: EntityId) -> Option<Self> {
+        let primary = if let Some(class) = entity::get_component(e, primary_ingredient()) {
+            IngredientPattern::Class(class)
+        } else {
+            IngredientPattern::Tag(entity::get_component(e, primary_ingredient_tag())?)
+        };
+
+        let secondary = if let Some(class) = entity::get_component(e, secondary_ingredient()) {
+            IngredientPattern::Class(class)
+        } else if let Some(tag) = entity::get_component(e, secondary_ingredient_tag()) {
+            IngredientPattern::Tag(tag)
+        } else if entity::has_component(e, any_secondary_ingredient()) {
+            IngredientPattern::Any
+        } else {
+            IngredientPattern::Class(EntityId::null())
+        };
 
-                store.insert(id.clone(), crafting_yield);
+        let outcomes = if let Some(primaries) = entity::get_component(e, primary_yields()) {
+            let secondaries = entity::get_component(e, secondary_yields()).unwrap_or_default();
+            let weights = entity::get_component(e, yield_weights()).unwrap_or_default();
+            primaries
+                .into_iter()
+                .enumerate()
+                .map(|(idx, primary)| CraftingOutcome {
+                    primary,
+                    secondary: secondaries.get(idx).copied().unwrap_or_default(),
+                    weight: weights.get(idx).copied().unwrap_or(1.0),
+                })
+                .collect()
+        } else {
+            vec![CraftingOutcome {
+                primary: entity::get_component(e, primary_yield()).unwrap_or_default(),
+                secondary: entity::get_component(e, secondary_yield()).unwrap_or_default(),
+                weight: 1.0,
+            }]
+        };
 
-                RegisterCraftingAction::new(id, primary_ingredient, secondary_ingredient)
-                    .send_local_broadcast(false);
+        Some(Self {
+            primary,
+            secondary,
+            outcomes,
+        })
+    }
+
+    pub fn specificity(&self) -> u8 {
+        self.primary.specificity() + self.secondary.specificity()
+    }
+
+    /// Randomly picks one of this recipe's outcomes according to their weights.
+    pub fn pick_outcome(&self) -> Option<&CraftingOutcome> {
+        let total: f32 = self.outcomes.iter().map(|outcome| outcome.weight).sum();
+        let mut roll = random::<f32>() * total;
+        for outcome in self.outcomes.iter() {
+            if roll < outcome.weight {
+                return Some(outcome);
             }
+
+            roll -= outcome.weight;
         }
-    });
 
-    OnAction::subscribe({
-        let store = store.clone();
-        move |_, data| {
-            if let Some(crafting_yield) = store.lock().unwrap().get(&data.id) {
-                let (primary, secondary) = if data.right_is_primary {
-                    (right_hand_ref(), left_hand_ref())
-                } else {
-                    (left_hand_ref(), right_hand_ref())
-                };
+        self.outcomes.last()
+    }
+}
 
-                let Some(primary) = entity::get_component(data.player, primary) else {
-                    return;
-                };
-                let Some(secondary) = entity::get_component(data.player, secondary) else {
-                    return;
-                };
+pub struct ItemInfo {
+    pub tags: Vec<String>,
+    pub is_tool: bool,
+}
+
+/// The ID of the crafting action that this module performs for every recipe.
+pub const CRAFT_ACTION_ID: &str = "craft";
+
+/// Looks up recipes by the items that players are holding.
+///
+/// A single crafting action matching any held items is registered for all of
+/// the recipes, and the held items are matched against each recipe's patterns
+/// when it's used.
+#[derive(Default)]
+pub struct CraftingStore {
+    /// Ordered so that ties between equally specific recipes are broken the
+    /// same way every time.
+    pub recipes: BTreeMap<EntityId, Recipe>,
+    pub items: HashMap<EntityId, ItemInfo>,
+
+    /// Whether the crafting action has been registered yet.
+    pub registered: bool,
+}
+
+impl CraftingStore {
+    /// Tests if an item class (or an empty hand) matches a pattern.
+    fn matches(&self, pattern: &IngredientPattern, class: EntityId) -> bool {
+        pattern.matches(class, self.items.get(&class))
+    }
+
+    /// Finds the most specific recipe for a pair of held items. Returns the
+    /// recipe and whether the first held item is the primary ingredient.
+    pub fn find_recipe(&self, first: EntityId, second: EntityId) -> Option<(&Recipe, bool)> {
+        let mut best: Option<(&Recipe, bool)> = None;
+        for recipe in self.recipes.values() {
+            let forward = self.matches(&recipe.primary, first)
+                && self.matches(&recipe.secondary, second);
+            let backward = self.matches(&recipe.primary, second)
+                && self.matches(&recipe.secondary, first);
+
+            if !forward && !backward {
+                continue;
+            }
+
+            let is_better = match best {
+                Some((old, _)) => old.specificity() < recipe.specificity(),
+                None => true,
+            };
+
+            if is_better {
+                best = Some((recipe, forward));
+            }
+        }
 
-                entity::add_component(primary, held_ref(), crafting_yield.primary);
+        best
+    }
 
-                if crafting_yield.write_secondary {
-                    entity::add_component(secondary, held_ref(), crafting_yield.secondary);
+    pub fn is_tool(&self, class: EntityId) -> bool {
+        self.items.get(&class).map(|item| item.is_tool) == Some(true)
+    }
+
+    /// Crafts with the items that a player is holding.
+    ///
+    /// Returns the reason that nothing was crafted if the craft was rejected.
+    pub fn craft(&self, player: EntityId) -> Result<(), String> {
+        let no_recipe = || "Nothing to craft with these items.".to_string();
+
+        let left = entity::get_component(player, left_hand_ref()).ok_or_else(no_recipe)?;
+        let right = entity::get_component(player, right_hand_ref()).ok_or_else(no_recipe)?;
+
+        let left_instance = entity::get_component(left, held_ref()).unwrap_or_default();
+        let right_instance = entity::get_component(right, held_ref()).unwrap_or_default();
+        let left_held = entity::get_component(left_instance, class()).unwrap_or_default();
+        let right_held = entity::get_component(right_instance, class()).unwrap_or_default();
+
+        // match both hands first, then each hand alone, since the player may
+        // be crafting with one hand while the other is full
+        let found = self
+            .find_recipe(left_held, right_held)
+            .map(|(recipe, left_is_primary)| (recipe, left_is_primary, true))
+            .or_else(|| {
+                let found = self.find_recipe(right_held, EntityId::null());
+                found.map(|(recipe, _)| (recipe, false, false))
+            })
+            .or_else(|| {
+                let found = self.find_recipe(left_held, EntityId::null());
+                found.map(|(recipe, _)| (recipe, true, false))
+            });
+
+        let (recipe, left_is_primary, uses_second) = found.ok_or_else(no_recipe)?;
+        let outcome = recipe.pick_outcome().ok_or_else(no_recipe)?;
+
+        let (primary, primary_held, secondary, secondary_held) = if left_is_primary {
+            (left, left_held, right, right_held)
+        } else {
+            (right, right_held, left, left_held)
+        };
+
+        // one of each ingredient is used up, and its yield replaces it in the
+        // hand. yields of ingredients that have more left go to the inventory
+        let mut uses = vec![(primary, primary_held, outcome.primary)];
+        if uses_second {
+            uses.push((secondary, secondary_held, outcome.secondary));
+        }
+
+        uses.retain(|(_hand, held, _yield_class)| !self.is_tool(*held));
+
+        let inventory = entity::get_component(player, inventory_ref());
+        for (hand, held, yield_class) in uses.iter().copied() {
+            let instance = entity::get_component(hand, held_ref()).unwrap_or_default();
+            let count = entity::get_component(instance, quantity()).unwrap_or(0);
+            let is_stored = count > 1 && !yield_class.is_null() && yield_class != held;
+            let has_room = inventory.map(|inventory| {
+                has_room_for(
+                    container_classes(),
+                    container_counts(),
+                    max_stack(),
+                    inventory,
+                    yield_class,
+                )
+            });
+            if is_stored && has_room != Some(true) {
+                return Err("Your inventory is full.".to_string());
+            }
+        }
+
+        for (hand, held, yield_class) in uses {
+            let instance = entity::get_component(hand, held_ref()).unwrap_or_default();
+            let count = entity::get_component(instance, quantity()).unwrap_or(0);
+
+            if count <= 1 {
+                let new_held = if yield_class.is_null() {
+                    EntityId::null()
+                } else {
+                    let uses = entity::get_component(yield_class, max_durability()).unwrap_or(0);
+                    spawn_instance(class(), quantity(), durability(), yield_class, 1, uses)
+                };
+
+                entity::add_component(hand, held_ref(), new_held);
+                if !instance.is_null() {
+                    entity::despawn_recursive(instance);
+                }
+            } else if yield_class != held {
+                entity::add_component(instance, quantity(), count - 1);
+                if let (false, Some(inventory)) = (yield_class.is_null(), inventory) {
+                    StoreItems::new(inventory, yield_class, 1).send_local_broadcast(false);
                 }
             }
         }
+
+        Ok(())
+    }
+}
+
+#[main]
+fn main() {
+    let store = Arc::new(Mutex::new(CraftingStore::default()));
+
+    store.on_event(spawn_query(is_recipe()), move |store, e, _| {
+        let Some(recipe) = Recipe::get(e) else {
+            eprintln!("recipe {} has no primary ingredient", e);
+            return;
+        };
+
+        store.recipes.insert(e, recipe);
+
+        if !store.registered {
+            store.registered = true;
+            RegisterCraftingAction::new(
+                CRAFT_ACTION_ID.to_string(),
+                EntityId::null(),
+                EntityId::null(),
+            )
+            .send_local_broadcast(false);
+        }
+    });
+
+    store.on_event(
+        spawn_query(()).requires(is_item()),
+        move |store, e, _| {
+            let item = ItemInfo {
+                tags: entity::get_component(e, item_tags()).unwrap_or_default(),
+                is_tool: entity::has_component(e, is_tool()),
+            };
+
+            store.items.insert(e, item);
+        },
+    );
+
+    store.on_local_message(move |store, _, data: OnAction| {
+        if data.id != CRAFT_ACTION_ID {
+            return;
+        }
+
+        let result = store.craft(data.player);
+
+        if let Some(uid) = entity::get_component(data.player, user_id()) {
+            let (success, reason) = match result {
+                Ok(()) => (true, String::new()),
+                Err(reason) => (false, reason),
+            };
+
+            CraftingResult::new(success, reason).send_client_targeted_reliable(uid);
+        }
     });
 }
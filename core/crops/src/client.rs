@@ -14,6 +14,7 @@ use packages::{
     map::components::position,
     region_networking::components::remote_entity,
     terrain::components::altitude,
+    things::components::model_prefab_url,
     this::{components::*, messages::*},
 };
 
@@ -21,6 +22,15 @@ mod shared;
 
 impl_remote_update!(UpdateCropCoords);
 
+/// Deterministically picks one of a crop's model variants for its coords.
+pub fn pick_variant(coords: IVec2, variant_num: usize) -> usize {
+    let x = (coords.x as u32).wrapping_mul(0x9e3779b1);
+    let y = (coords.y as u32).wrapping_mul(0x85ebca77);
+    let hash = x ^ y;
+    let hash = hash ^ (hash >> 16);
+    hash as usize % variant_num
+}
+
 #[main]
 fn main() {
     shared::init_shared();
@@ -31,15 +41,42 @@ fn main() {
         entity::add_component(e, coords(), data.position);
     });
 
+    for category in [is_small_crop(), is_medium_crop()] {
+        spawn_query(coords())
+            .requires(category)
+            .bind(move |entities| {
+                for (e, coords) in entities {
+                    let new_position = coords.as_vec2() + 0.5;
+                    entity::add_component(e, position(), new_position);
+                }
+            });
+    }
+
+    // large crops are centered on their footprint
     spawn_query(coords())
-        .requires(is_medium_crop())
+        .requires(is_large_crop())
         .bind(move |entities| {
             for (e, coords) in entities {
-                let new_position = coords.as_vec2() + 0.5;
+                let footprint = entity::get_component(e, large_crop_footprint())
+                    .unwrap_or(UVec2::ONE)
+                    .as_vec2();
+
+                let new_position = coords.as_vec2() + footprint / 2.0;
                 entity::add_component(e, position(), new_position);
             }
         });
 
+    spawn_query((coords(), model_variants())).bind(move |entities| {
+        for (e, (coords, variants)) in entities {
+            if variants.is_empty() {
+                continue;
+            }
+
+            let variant = &variants[pick_variant(coords, variants.len())];
+            entity::add_component(e, model_prefab_url(), variant.clone());
+        }
+    });
+
     // despawn old crops once the new one has finished loading
     spawn_query((despawn_when_loaded(), spawned())).bind(move |entities| {
         for (e, (old, _)) in entities {
@@ -59,29 +96,23 @@ fn main() {
         }
     });
 
-    spawn_query((position(), altitude()))
-        .requires(is_medium_crop())
-        .bind(move |entities| {
-            for (e, (position, altitude)) in entities {
-                // pseudo-randomly generate the angle so that when a crop on
-                // this tile grows it doesn't also rotate
-                let angle = position.dot(vec2(12.9898, 78.233)) * 43758.5453;
-
-                entity::add_components(
-                    e,
-                    make_transformable()
-                        .with(translation(), position.extend(altitude))
-                        .with(rotation(), Quat::from_rotation_z(angle))
-                        .with(local_to_world(), Mat4::IDENTITY),
-                );
-            }
-        });
+    for category in [is_small_crop(), is_medium_crop(), is_large_crop()] {
+        spawn_query((position(), altitude()))
+            .requires(category)
+            .bind(move |entities| {
+                for (e, (position, altitude)) in entities {
+                    // pseudo-randomly generate the angle so that when a crop on
+                    // this tile grows it doesn't also rotate
+                    let angle = position.dot(vec2(12.9898, 78.233)) * 43758.5453;
 
-    despawn_query(medium_crop_occupant()).bind(move |entities| {
-        for (_, occupant) in entities {
-            if !occupant.is_null() {
-                entity::despawn_recursive(occupant);
-            }
-        }
-    });
+                    entity::add_components(
+                        e,
+                        make_transformable()
+                            .with(translation(), position.extend(altitude))
+                            .with(rotation(), Quat::from_rotation_z(angle))
+                            .with(local_to_world(), Mat4::IDENTITY),
+                    );
+                }
+            });
+    }
 }
@@ -1,14 +1,545 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
 use ambient_api::prelude::*;
 
 use packages::{
     map::components::*,
     region_networking::messages::OnSpawnThing,
+    soil::components::{is_tilled, soil_fertility, soil_moisture},
+    terrain::components::is_water,
     things::components::class_ref,
     this::{components::*, messages::*},
+    weather::components::{season, temperature},
 };
 
 mod shared;
 
+/// How much each inhibiting neighbor adds to a crop's growth age.
+pub const INHIBITOR_SLOWDOWN: f32 = 0.25;
+
+/// How much each companion neighbor takes away from a crop's growth age.
+pub const COMPANION_SPEEDUP: f32 = 0.1;
+
+/// The most that companion neighbors can take away from a crop's growth age.
+pub const MAX_COMPANION_SPEEDUP: f32 = 0.5;
+
+/// How much completely dry soil adds to a crop's growth age.
+pub const DRY_SOIL_SLOWDOWN: f32 = 1.0;
+
+/// How much completely depleted soil adds to a crop's growth age.
+pub const INFERTILE_SOIL_SLOWDOWN: f32 = 1.0;
+
+/// Soil at least this moist doesn't slow crops down.
+pub const WELL_WATERED_MOISTURE: f32 = 0.5;
+
+/// Soil at least this fertile doesn't slow crops down.
+pub const WELL_FED_FERTILITY: f32 = 0.5;
+
+/// The number of steps that soil moisture and fertility are divided into below
+/// their well-watered and well-fed levels. Soil only affects growth once it
+/// crosses into another step, so that crops aren't rescheduled every soil tick.
+pub const SOIL_GROWTH_STEPS: u8 = 5;
+
+/// How much tilled soil takes away from a crop's growth age.
+pub const TILLED_SPEEDUP: f32 = 0.2;
+
+/// The default value of a crop class's fertility_use.
+pub const DEFAULT_FERTILITY_USE: f32 = 0.05;
+
+/// The time in seconds between each growth tick.
+pub const GROWTH_TICK_INTERVAL: f32 = 0.1;
+
+/// How long a crop that's old enough to grow, but is unpollinated or out of
+/// season, waits before trying again.
+pub const GROWTH_RETRY_TICKS: u32 = 50;
+
+/// How much each degree outside of a crop's comfortable temperature range adds
+/// to its growth age.
+pub const TEMPERATURE_SLOWDOWN: f32 = 0.1;
+
+/// The most seeding attempts a crop makes at once when catching up on the
+/// time that its chunk was unloaded.
+pub const MAX_CATCH_UP_SEEDINGS: u32 = 4;
+
+/// Returns the (up to) eight loaded tiles surrounding a tile, including diagonals.
+pub fn surrounding_tiles(tile: EntityId) -> Vec<EntityId> {
+    let mut tiles = Vec::with_capacity(8);
+    for (side, corner) in [
+        (north_neighbor(), east_neighbor()),
+        (east_neighbor(), south_neighbor()),
+        (south_neighbor(), west_neighbor()),
+        (west_neighbor(), north_neighbor()),
+    ] {
+        let Some(neighbor) = entity::get_component(tile, side) else {
+            continue;
+        };
+
+        tiles.push(neighbor);
+
+        if let Some(diagonal) = entity::get_component(neighbor, corner) {
+            tiles.push(diagonal);
+        }
+    }
+
+    tiles
+}
+
+fn has_occupant(tile: EntityId, occupant: Component<EntityId>) -> bool {
+    !entity::get_component(tile, occupant)
+        .unwrap_or_default()
+        .is_null()
+}
+
+/// Tests if a small crop can spread onto a tile.
+fn can_spread_onto(tile: EntityId, is_cover: bool) -> bool {
+    if has_occupant(tile, small_crop_occupant()) || has_occupant(tile, large_crop_occupant()) {
+        return false;
+    }
+
+    if entity::has_component(tile, is_water()) {
+        return false;
+    }
+
+    // only cover crops may grow underneath medium crops
+    is_cover || !has_occupant(tile, medium_crop_occupant())
+}
+
+/// Returns the crops occupying a tile on every layer.
+fn get_occupants(tile: EntityId) -> impl Iterator<Item = EntityId> {
+    [
+        small_crop_occupant(),
+        medium_crop_occupant(),
+        large_crop_occupant(),
+    ]
+    .into_iter()
+    .filter_map(move |occupant| entity::get_component(tile, occupant))
+    .filter(|occupant| !occupant.is_null() && entity::exists(*occupant))
+}
+
+/// Recounts the tags of the crops surrounding a tile.
+pub fn update_neighbor_counts(tile: EntityId) {
+    if !entity::exists(tile) {
+        return;
+    }
+
+    // crops that cover this tile too aren't its neighbors, and large crops
+    // covering several neighboring tiles are only counted once
+    let mut seen: HashSet<EntityId> = get_occupants(tile).collect();
+    let mut counts = BTreeMap::<String, u8>::new();
+    for neighbor in surrounding_tiles(tile) {
+        for occupant in get_occupants(neighbor) {
+            if !seen.insert(occupant) {
+                continue;
+            }
+
+            for tag in entity::get_component(occupant, crop_tags()).unwrap_or_default() {
+                let count = counts.entry(tag).or_default();
+                *count = count.saturating_add(1);
+            }
+        }
+    }
+
+    let (tags, counts): (Vec<_>, Vec<_>) = counts.into_iter().unzip();
+    entity::add_component(tile, neighbor_tags(), tags);
+    entity::add_component(tile, neighbor_tag_counts(), counts);
+}
+
+/// Recounts the neighbors of a set of tiles and all of the tiles around them.
+///
+/// Returns every tile that was recounted.
+pub fn update_neighbor_counts_around(tiles: &[EntityId]) -> HashSet<EntityId> {
+    let mut updated = HashSet::new();
+    for tile in tiles.iter().copied() {
+        for tile in surrounding_tiles(tile).into_iter().chain(Some(tile)) {
+            if updated.insert(tile) {
+                update_neighbor_counts(tile);
+            }
+        }
+    }
+
+    updated
+}
+
+/// Gets the number of crops around a tile with the given tag.
+pub fn get_neighbor_count(tile: EntityId, tag: &str) -> u8 {
+    let tags = entity::get_component(tile, neighbor_tags()).unwrap_or_default();
+    let counts = entity::get_component(tile, neighbor_tag_counts()).unwrap_or_default();
+    match tags.binary_search_by(|other| other.as_str().cmp(tag)) {
+        Ok(idx) => counts.get(idx).copied().unwrap_or(0),
+        Err(_) => 0,
+    }
+}
+
+/// Sums the number of crops around a tile with any of the given tags.
+pub fn sum_neighbor_counts(tile: EntityId, tags: &[String]) -> u32 {
+    tags.iter()
+        .map(|tag| get_neighbor_count(tile, tag) as u32)
+        .sum()
+}
+
+/// Tests if a crop has enough neighbors to pollinate, if it needs any.
+pub fn is_pollinated(e: EntityId, tile: EntityId) -> bool {
+    let Some(tag) = entity::get_component(e, pollination_tag()) else {
+        return true;
+    };
+
+    let threshold = entity::get_component(e, pollination_threshold()).unwrap_or(1);
+    get_neighbor_count(tile, &tag) >= threshold
+}
+
+/// Rounds a soil value down to the growth step that it's in.
+fn get_soil_step(value: f32, full: f32) -> u8 {
+    let steps = SOIL_GROWTH_STEPS as f32;
+    ((value / full).clamp(0.0, 1.0) * steps).floor() as u8
+}
+
+/// Gets the growth steps of a tile's soil moisture and fertility.
+pub fn get_soil_steps(tile: EntityId) -> (u8, u8) {
+    let moisture = entity::get_component(tile, soil_moisture()).unwrap_or(WELL_WATERED_MOISTURE);
+    let fertility = entity::get_component(tile, soil_fertility()).unwrap_or(WELL_FED_FERTILITY);
+    (
+        get_soil_step(moisture, WELL_WATERED_MOISTURE),
+        get_soil_step(fertility, WELL_FED_FERTILITY),
+    )
+}
+
+/// Calculates how much a tile's soil multiplies the growth age of its crops.
+pub fn get_soil_multiplier(tile: EntityId) -> f32 {
+    let (moisture, fertility) = get_soil_steps(tile);
+    let steps = SOIL_GROWTH_STEPS as f32;
+    let dryness = 1.0 - moisture as f32 / steps;
+    let depletion = 1.0 - fertility as f32 / steps;
+
+    let tilled = if entity::has_component(tile, is_tilled()) {
+        TILLED_SPEEDUP
+    } else {
+        0.0
+    };
+
+    let dry = 1.0 + dryness * DRY_SOIL_SLOWDOWN;
+    let infertile = 1.0 + depletion * INFERTILE_SOIL_SLOWDOWN;
+    dry * infertile * (1.0 - tilled)
+}
+
+/// Tests if it's currently one of a crop's allowed seasons, if it has any.
+pub fn is_in_season(e: EntityId) -> bool {
+    let Some(seasons) = entity::get_component(e, allowed_seasons()) else {
+        return true;
+    };
+
+    let current = entity::get_component(entity::synchronized_resources(), season()).unwrap_or(0);
+    seasons.contains(&current)
+}
+
+/// Gets the temperature of the chunk that a crop is growing in.
+pub fn get_crop_temperature(e: EntityId) -> Option<f32> {
+    let chunk = entity::get_component(e, in_chunk())?;
+    entity::get_component(chunk, temperature())
+}
+
+/// Tests if a temperature is within a (min, max) range.
+fn is_in_range(range: Vec2, value: f32) -> bool {
+    value >= range.x && value <= range.y
+}
+
+/// Calculates how much the temperature multiplies a crop's growth age.
+pub fn get_temperature_multiplier(e: EntityId) -> f32 {
+    let Some(range) = entity::get_component(e, comfortable_temperature()) else {
+        return 1.0;
+    };
+
+    let Some(temp) = get_crop_temperature(e) else {
+        return 1.0;
+    };
+
+    let degrees = (range.x - temp).max(temp - range.y).max(0.0);
+    1.0 + degrees * TEMPERATURE_SLOWDOWN
+}
+
+/// Calculates the age that a crop needs to reach to grow, adjusted for its
+/// inhibiting and companion neighbors, its soil, and the temperature.
+pub fn get_growth_age(e: EntityId, tile: EntityId, base_age: u16) -> u16 {
+    let inhibitors = entity::get_component(e, inhibitor_tags()).unwrap_or_default();
+    let companions = entity::get_component(e, companion_tags()).unwrap_or_default();
+
+    let slowdown = sum_neighbor_counts(tile, &inhibitors) as f32 * INHIBITOR_SLOWDOWN;
+    let speedup = sum_neighbor_counts(tile, &companions) as f32 * COMPANION_SPEEDUP;
+    let speedup = speedup.min(MAX_COMPANION_SPEEDUP);
+    let soil = get_soil_multiplier(tile);
+    let temperature = get_temperature_multiplier(e);
+
+    let age = base_age as f32 * (1.0 + slowdown) * (1.0 - speedup) * soil * temperature;
+    age.round().clamp(1.0, u16::MAX as f32) as u16
+}
+
+/// Takes the nutrients that a crop needs to grow out of the soil that it covers.
+fn consume_fertility(e: EntityId, tile: EntityId) {
+    let used = entity::get_component(e, fertility_use()).unwrap_or(DEFAULT_FERTILITY_USE);
+    let tiles = entity::get_component(e, large_crop_tiles()).unwrap_or_else(|| vec![tile]);
+    for tile in tiles {
+        let Some(fertility) = entity::get_component(tile, soil_fertility()) else {
+            continue;
+        };
+
+        let fertility = (fertility - used).clamp(0.0, 1.0);
+        entity::set_component(tile, soil_fertility(), fertility);
+    }
+}
+
+pub fn get_growth_tick() -> u32 {
+    entity::get_component(entity::resources(), growth_tick()).unwrap_or(0)
+}
+
+/// Plants a medium crop's seed on a random empty neighboring tile.
+fn seed_medium_crop(tile: EntityId, seed: EntityId) {
+    let mut neighbors = [
+        north_neighbor(),
+        east_neighbor(),
+        south_neighbor(),
+        west_neighbor(),
+    ];
+
+    let mut rng = thread_rng();
+    neighbors.shuffle(&mut rng);
+
+    for neighbor in neighbors {
+        let Some(neighbor) = entity::get_component(tile, neighbor) else {
+            continue;
+        };
+
+        if has_occupant(neighbor, medium_crop_occupant())
+            || has_occupant(neighbor, large_crop_occupant())
+        {
+            continue;
+        }
+
+        if entity::has_component(neighbor, is_water()) {
+            continue;
+        }
+
+        Entity::new()
+            .with(is_medium_crop(), ())
+            .with(class_ref(), seed)
+            .with(on_tile(), neighbor)
+            .spawn();
+
+        break;
+    }
+}
+
+/// Spreads a small crop onto a random surrounding tile, unless its
+/// surroundings are already too crowded.
+fn seed_small_crop(e: EntityId, tile: EntityId, seed: EntityId) {
+    let surrounding = surrounding_tiles(tile);
+    let occupied = surrounding
+        .iter()
+        .filter(|tile| has_occupant(**tile, small_crop_occupant()))
+        .count();
+
+    let density = occupied as f32 / 8.0;
+    let max_density = entity::get_component(e, max_spread_density()).unwrap_or(1.0);
+    if density >= max_density {
+        return;
+    }
+
+    let is_cover = entity::has_component(e, is_cover_crop());
+    let Some(target) = surrounding
+        .into_iter()
+        .filter(|tile| can_spread_onto(*tile, is_cover))
+        .choose(&mut thread_rng())
+    else {
+        return;
+    };
+
+    Entity::new()
+        .with(is_small_crop(), ())
+        .with(class_ref(), seed)
+        .with(on_tile(), target)
+        .spawn();
+}
+
+/// Replaces a crop with its next growth stage, or removes it if it has none.
+fn grow_crop(e: EntityId, tile: EntityId, next: EntityId, birth: u32) {
+    // large crops are anchored to their first tile, so they can be respawned
+    // the same way as medium and small crops
+    let category = if entity::has_component(e, is_large_crop()) {
+        is_large_crop()
+    } else if entity::has_component(e, is_small_crop()) {
+        is_small_crop()
+    } else {
+        is_medium_crop()
+    };
+
+    consume_fertility(e, tile);
+    entity::despawn_recursive(e);
+
+    if !next.is_null() {
+        Entity::new()
+            .with(category, ())
+            .with(class_ref(), next)
+            .with(on_tile(), tile)
+            .with(birth_tick(), birth)
+            .with(last_growth_tick(), birth)
+            .spawn();
+    }
+}
+
+/// Calculates the growth tick of a crop's next growth or seeding event.
+fn get_next_event(e: EntityId, tile: EntityId, now: u32) -> Option<u32> {
+    let birth = entity::get_component(e, birth_tick())?;
+
+    // stages without a next stage are terminal, whatever their growth age
+    let next_age = entity::get_component(e, next_growth_age())
+        .filter(|_| entity::has_component(e, next_growth_stage()));
+
+    let growth = next_age.map(|next_age| {
+        let due = birth.saturating_add(get_growth_age(e, tile, next_age) as u32);
+        if due > now {
+            due
+        } else if is_pollinated(e, tile) && is_in_season(e) {
+            // overdue crops grow right away, so that crops in unloaded chunks
+            // catch up through every stage at once
+            now
+        } else {
+            // the crop is old enough but can't grow yet
+            now + GROWTH_RETRY_TICKS
+        }
+    });
+
+    let seeding = entity::get_component(e, seeding_interval())
+        .filter(|interval| *interval > 0 && entity::has_component(e, seed()))
+        .map(|interval| {
+            let interval = interval as u32;
+            let age = now.saturating_sub(birth);
+            birth + (age / interval + 1) * interval
+        });
+
+    match (growth, seeding) {
+        (Some(growth), Some(seeding)) => Some(growth.min(seeding)),
+        (growth, seeding) => growth.or(seeding),
+    }
+}
+
+/// Wakes crops up only when their next growth or seeding event is due.
+#[derive(Default)]
+pub struct GrowthScheduler {
+    /// The crops due at each growth tick, batched by chunk. Entries become
+    /// stale when crops are rescheduled or removed and are skipped when due.
+    queue: BTreeMap<u32, HashMap<EntityId, Vec<EntityId>>>,
+
+    /// The chunk and due tick of every scheduled crop.
+    crops: HashMap<EntityId, (EntityId, u32)>,
+}
+
+impl GrowthScheduler {
+    /// Schedules (or reschedules) a crop's next event.
+    pub fn schedule(&mut self, e: EntityId, now: u32) {
+        let Some(tile) = entity::get_component(e, on_tile()) else {
+            self.unschedule(e);
+            return;
+        };
+
+        let Some(chunk) = entity::get_component(e, in_chunk()) else {
+            self.unschedule(e);
+            return;
+        };
+
+        let Some(due) = get_next_event(e, tile, now) else {
+            self.unschedule(e);
+            return;
+        };
+
+        if self.crops.get(&e) == Some(&(chunk, due)) {
+            return;
+        }
+
+        self.crops.insert(e, (chunk, due));
+        self.queue
+            .entry(due)
+            .or_default()
+            .entry(chunk)
+            .or_default()
+            .push(e);
+    }
+
+    pub fn unschedule(&mut self, e: EntityId) {
+        self.crops.remove(&e);
+    }
+
+    /// Removes and returns every crop that's due by `now`, batched by chunk.
+    pub fn pop_due(&mut self, now: u32) -> HashMap<EntityId, Vec<EntityId>> {
+        let mut due = HashMap::<EntityId, Vec<EntityId>>::new();
+        let later = self.queue.split_off(&(now + 1));
+        let ready = std::mem::replace(&mut self.queue, later);
+
+        for (tick, chunks) in ready {
+            for (chunk, crops) in chunks {
+                for e in crops {
+                    if self.crops.get(&e) != Some(&(chunk, tick)) {
+                        continue;
+                    }
+
+                    self.crops.remove(&e);
+                    due.entry(chunk).or_default().push(e);
+                }
+            }
+        }
+
+        due
+    }
+
+    /// Processes a crop's due events, then schedules its next ones.
+    pub fn process(&mut self, e: EntityId, now: u32) {
+        let Some(tile) = entity::get_component(e, on_tile()) else {
+            return;
+        };
+
+        let Some(birth) = entity::get_component(e, birth_tick()) else {
+            return;
+        };
+
+        let last = entity::get_component(e, last_growth_tick()).unwrap_or(birth);
+        entity::add_component(e, last_growth_tick(), now);
+
+        // crops only seed and grow when pollinated and in season
+        let can_grow = is_pollinated(e, tile) && is_in_season(e);
+
+        // seed once for every interval since this crop was last processed
+        let interval = entity::get_component(e, seeding_interval()).unwrap_or(0) as u32;
+        let seed = entity::get_component(e, seed());
+        if let (true, Some(seed)) = (interval > 0 && can_grow, seed) {
+            let seedings = now.saturating_sub(birth) / interval;
+            let seeded = last.saturating_sub(birth) / interval;
+            let is_small = entity::has_component(e, is_small_crop());
+            for _ in 0..seedings.saturating_sub(seeded).min(MAX_CATCH_UP_SEEDINGS) {
+                if is_small {
+                    seed_small_crop(e, tile, seed);
+                } else if entity::has_component(e, is_medium_crop()) {
+                    seed_medium_crop(tile, seed);
+                }
+            }
+        }
+
+        let next_age = entity::get_component(e, next_growth_age());
+        let next = entity::get_component(e, next_growth_stage());
+        if let (Some(next_age), Some(next)) = (next_age, next) {
+            let growth_age = get_growth_age(e, tile, next_age) as u32;
+
+            if now.saturating_sub(birth) >= growth_age && can_grow {
+                // the next stage is as old as the time since this one grew, so
+                // that crops in unloaded chunks catch up through every stage
+                grow_crop(e, tile, next, birth + growth_age);
+                return;
+            }
+        }
+
+        self.schedule(e, now);
+    }
+}
+
 #[main]
 fn main() {
     shared::init_shared();
@@ -31,87 +562,214 @@ fn main() {
         }
     });
 
-    run_async(async move {
-        let all_crops = query((is_medium_crop(), on_tile(), age())).build();
-        loop {
-            sleep(0.1).await;
+    let scheduler = Arc::new(Mutex::new(GrowthScheduler::default()));
+
+    if !entity::has_component(entity::resources(), growth_tick()) {
+        entity::add_component(entity::resources(), growth_tick(), 0);
+    }
+
+    run_async({
+        let scheduler = scheduler.clone();
+        async move {
+            loop {
+                sleep(GROWTH_TICK_INTERVAL).await;
+
+                let now = get_growth_tick() + 1;
+                entity::add_component(entity::resources(), growth_tick(), now);
+
+                let mut scheduler = scheduler.lock().unwrap();
+                for (chunk, crops) in scheduler.pop_due(now) {
+                    // crops in unloaded chunks are caught up when they reload
+                    if !entity::exists(chunk) {
+                        continue;
+                    }
+
+                    for e in crops {
+                        scheduler.process(e, now);
+                    }
+                }
+            }
+        }
+    });
+
+    // schedule crops once they've been attached to their tiles
+    for category in [is_small_crop(), is_medium_crop(), is_large_crop()] {
+        spawn_query((in_chunk(), on_tile()))
+            .requires(category)
+            .bind({
+                let scheduler = scheduler.clone();
+                move |entities| {
+                    let now = get_growth_tick();
+                    let mut scheduler = scheduler.lock().unwrap();
+                    for (e, _) in entities {
+                        // crops restored from a save may only have an age
+                        if !entity::has_component(e, birth_tick()) {
+                            let age = entity::get_component(e, age()).unwrap_or(0) as u32;
+                            let birth = now.saturating_sub(age);
+                            entity::add_component(e, birth_tick(), birth);
+                            entity::add_component(e, last_growth_tick(), birth);
+                        }
+
+                        scheduler.schedule(e, now);
+                    }
+                }
+            });
+    }
 
-            for (e, (_medium, _tile, old_age)) in all_crops.evaluate() {
-                let new_age = old_age + 1;
-                entity::set_component(e, age(), new_age);
+    despawn_query(on_tile()).bind({
+        let scheduler = scheduler.clone();
+        move |entities| {
+            let mut scheduler = scheduler.lock().unwrap();
+            for (e, _) in entities {
+                scheduler.unschedule(e);
             }
         }
     });
 
-    change_query((
-        is_medium_crop(),
-        on_tile(),
-        age(),
-        seeding_interval(),
-        seed(),
-    ))
-    .track_change(age())
-    .bind(move |entities| {
-        for (_e, (_, tile, age, interval, seed)) in entities {
-            if age == 0 || age % interval != 0 {
-                continue;
+    // neighbors affect growth, so reschedule crops whenever their neighbors change
+    let reschedule_tiles = {
+        let scheduler = scheduler.clone();
+        move |tiles: HashSet<EntityId>| {
+            let now = get_growth_tick();
+            let mut scheduler = scheduler.lock().unwrap();
+            for tile in tiles {
+                for occupant in get_occupants(tile) {
+                    scheduler.schedule(occupant, now);
+                }
             }
+        }
+    };
 
-            let mut neighbors = [
-                north_neighbor(),
-                east_neighbor(),
-                south_neighbor(),
-                west_neighbor(),
-            ];
+    // soil affects growth too, so reschedule crops whenever their soil crosses
+    // into another growth step
+    let soil_steps = Arc::new(Mutex::new(HashMap::<EntityId, (u8, u8)>::new()));
 
-            let mut rng = thread_rng();
-            neighbors.shuffle(&mut rng);
+    spawn_query((soil_moisture(), soil_fertility())).bind({
+        let soil_steps = soil_steps.clone();
+        move |entities| {
+            let mut soil_steps = soil_steps.lock().unwrap();
+            for (tile, _) in entities {
+                soil_steps.insert(tile, get_soil_steps(tile));
+            }
+        }
+    });
 
-            for neighbor in neighbors {
-                let Some(neighbor) = entity::get_component(tile, neighbor) else {
-                    continue;
-                };
+    despawn_query((soil_moisture(), soil_fertility())).bind({
+        let soil_steps = soil_steps.clone();
+        move |entities| {
+            let mut soil_steps = soil_steps.lock().unwrap();
+            for (tile, _) in entities {
+                soil_steps.remove(&tile);
+            }
+        }
+    });
 
-                if !entity::get_component(neighbor, medium_crop_occupant())
-                    .unwrap_or_default()
-                    .is_null()
-                {
-                    continue;
-                }
+    change_query((soil_moisture(), soil_fertility()))
+        .track_change((soil_moisture(), soil_fertility()))
+        .bind({
+            let reschedule_tiles = reschedule_tiles.clone();
+            move |entities| {
+                let mut soil_steps = soil_steps.lock().unwrap();
+                let changed = entities
+                    .into_iter()
+                    .map(|(tile, _)| tile)
+                    .filter(|tile| {
+                        let steps = get_soil_steps(*tile);
+                        soil_steps.insert(*tile, steps) != Some(steps)
+                    })
+                    .collect();
+
+                reschedule_tiles(changed);
+            }
+        });
+
+    spawn_query(())
+        .requires(is_tilled())
+        .bind({
+            let reschedule_tiles = reschedule_tiles.clone();
+            move |entities| {
+                reschedule_tiles(entities.into_iter().map(|(tile, _)| tile).collect());
+            }
+        });
+
+    despawn_query(())
+        .requires(is_tilled())
+        .bind({
+            let reschedule_tiles = reschedule_tiles.clone();
+            move |entities| {
+                reschedule_tiles(entities.into_iter().map(|(tile, _)| tile).collect());
+            }
+        });
 
-                Entity::new()
-                    .with(is_medium_crop(), ())
-                    .with(class_ref(), seed)
-                    .with(on_tile(), neighbor)
-                    .spawn();
+    // crops die when it gets too hot or too cold for them, and otherwise grow
+    // faster or slower as the temperature changes
+    change_query((temperature(), chunk_tile_refs()))
+        .track_change(temperature())
+        .bind({
+            let scheduler = scheduler.clone();
+            move |entities| {
+                let now = get_growth_tick();
+                let mut scheduler = scheduler.lock().unwrap();
+                for (chunk, (temp, tiles)) in entities {
+                    // large crops are found on each of their tiles but only
+                    // belong to the chunk that they're anchored in
+                    let crops: HashSet<EntityId> = tiles
+                        .into_iter()
+                        .flat_map(get_occupants)
+                        .filter(|e| entity::get_component(*e, in_chunk()) == Some(chunk))
+                        .collect();
 
-                break;
+                    for e in crops {
+                        let survivable = entity::get_component(e, survivable_temperature());
+                        if let Some(range) = survivable {
+                            if !is_in_range(range, temp) {
+                                entity::despawn_recursive(e);
+                                continue;
+                            }
+                        }
+
+                        if entity::has_component(e, comfortable_temperature()) {
+                            scheduler.schedule(e, now);
+                        }
+                    }
+                }
+            }
+        });
+
+    // keep the neighbor counts of the tiles around crops up-to-date
+    spawn_query((in_chunk(), on_tile(), crop_tags())).bind({
+        let reschedule_tiles = reschedule_tiles.clone();
+        move |entities| {
+            for (e, (_chunk, tile, _tags)) in entities {
+                let tiles =
+                    entity::get_component(e, large_crop_tiles()).unwrap_or_else(|| vec![tile]);
+                reschedule_tiles(update_neighbor_counts_around(&tiles));
             }
         }
     });
 
-    change_query((
-        is_medium_crop(),
-        on_tile(),
-        age(),
-        next_growth_age(),
-        next_growth_stage(),
-    ))
-    .track_change(age())
-    .bind(move |entities| {
-        for (e, (_, tile, current_age, next_age, next)) in entities {
-            if current_age < next_age {
-                continue;
+    despawn_query((on_tile(), crop_tags()))
+        .excludes(is_large_crop())
+        .bind({
+            let reschedule_tiles = reschedule_tiles.clone();
+            move |entities| {
+                for (_e, (tile, _tags)) in entities {
+                    reschedule_tiles(update_neighbor_counts_around(&[tile]));
+                }
             }
+        });
 
-            entity::despawn_recursive(e);
+    despawn_query((large_crop_tiles(), crop_tags())).bind(move |entities| {
+        for (_e, (tiles, _tags)) in entities {
+            reschedule_tiles(update_neighbor_counts_around(&tiles));
+        }
+    });
 
-            if !next.is_null() {
-                Entity::new()
-                    .with(is_medium_crop(), ())
-                    .with(class_ref(), next)
-                    .with(on_tile(), tile)
-                    .spawn();
+    // count the crops around the tiles of newly-loaded chunks
+    spawn_query(chunk_tile_refs()).bind(move |entities| {
+        for (_chunk, tiles) in entities {
+            for tile in tiles {
+                update_neighbor_counts(tile);
             }
         }
     });
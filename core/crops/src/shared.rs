@@ -1,60 +1,289 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
 use ambient_api::prelude::*;
 
 use crate::packages::{
-    map::components::{chunk, chunk_tile_index, chunk_tile_refs, in_chunk},
+    map::components::{blocks_movement, chunk, chunk_tile_index, chunk_tile_refs, in_chunk},
+    terrain::components::is_water,
     this::components::*,
 };
-use flowerpot_common::{init_map, SystemExt, CHUNK_SIZE};
+use flowerpot_common::{init_map, CHUNK_SIZE};
+
+/// Attaches a medium or small crop to the tile at its coordinates in a loaded
+/// chunk, using the given occupant component for the crop's layer.
+fn attach_crop(
+    e: EntityId,
+    coords: IVec2,
+    chunk: EntityId,
+    tiles: &[EntityId],
+    occupant: Component<EntityId>,
+) {
+    let xy = coords.div_euclid(IVec2::splat(CHUNK_SIZE as i32));
+    let fine = coords - xy * CHUNK_SIZE as i32;
+    let tile_idx = fine.y * CHUNK_SIZE as i32 + fine.x;
+
+    let Ok(tile_idx) = TryInto::<u8>::try_into(tile_idx) else {
+        eprintln!("tile index {} is out-of-bounds", tile_idx);
+        return;
+    };
+
+    let Some(tile) = tiles.get(tile_idx as usize).copied() else {
+        eprintln!("chunk {} has no tile {}", chunk, tile_idx);
+        return;
+    };
+
+    // crops can't grow underwater
+    if entity::has_component(tile, is_water()) {
+        entity::despawn_recursive(e);
+        return;
+    }
+
+    let old_occupant = entity::get_component(tile, occupant).unwrap_or_default();
+
+    let old_occupant = if old_occupant == e {
+        EntityId::null()
+    } else {
+        old_occupant
+    };
+
+    if !old_occupant.is_null() {
+        entity::despawn_recursive(old_occupant);
+    }
+
+    entity::add_components(
+        e,
+        Entity::new()
+            .with(in_chunk(), chunk)
+            .with(chunk_tile_index(), tile_idx)
+            .with(on_tile(), tile)
+            .with(despawn_when_loaded(), old_occupant),
+    );
+
+    entity::add_component(tile, occupant, e);
+
+    // planting a medium crop clears out any small crops that aren't meant to
+    // grow underneath it
+    if entity::has_component(e, is_medium_crop()) {
+        let small = entity::get_component(tile, small_crop_occupant()).unwrap_or_default();
+        if !small.is_null() && !entity::has_component(small, is_cover_crop()) {
+            entity::despawn_recursive(small);
+        }
+    }
+}
+
+/// Attaches a large crop to every tile in its footprint, replacing any crops
+/// that were already on those tiles.
+fn attach_large_crop(e: EntityId, coords: IVec2, chunk: EntityId, tiles: &[EntityId]) {
+    let footprint = entity::get_component(e, large_crop_footprint())
+        .unwrap_or(UVec2::ONE)
+        .as_ivec2();
+
+    let chunk_size = IVec2::splat(CHUNK_SIZE as i32);
+    let min = coords.rem_euclid(chunk_size);
+    let max = min + footprint;
+    if max.cmpgt(chunk_size).any() {
+        eprintln!("large crop {} at {} crosses a chunk boundary", e, coords);
+        entity::despawn_recursive(e);
+        return;
+    }
+
+    let mut covered = Vec::with_capacity((footprint.x * footprint.y) as usize);
+    for y in min.y..max.y {
+        for x in min.x..max.x {
+            covered.push(tiles[(y * CHUNK_SIZE as i32 + x) as usize]);
+        }
+    }
+
+    // large crops can't grow underwater either
+    if covered
+        .iter()
+        .any(|tile| entity::has_component(*tile, is_water()))
+    {
+        entity::despawn_recursive(e);
+        return;
+    }
+
+    // keep the previous growth stage around until this one's model loads, but
+    // clear out everything else underneath this crop straight away
+    let mut old_occupant = EntityId::null();
+    for tile in covered.iter().copied() {
+        let old_large = entity::get_component(tile, large_crop_occupant()).unwrap_or_default();
+        if !old_large.is_null() && old_large != e && old_large != old_occupant {
+            if old_occupant.is_null() {
+                old_occupant = old_large;
+            } else {
+                entity::despawn_recursive(old_large);
+            }
+        }
+
+        for occupant in [medium_crop_occupant(), small_crop_occupant()] {
+            let old = entity::get_component(tile, occupant).unwrap_or_default();
+            if !old.is_null() {
+                entity::despawn_recursive(old);
+            }
+        }
+
+        entity::add_component(tile, large_crop_occupant(), e);
+        entity::add_component(tile, blocks_movement(), ());
+    }
+
+    let min_idx = (min.y * CHUNK_SIZE as i32 + min.x) as u8;
+    entity::add_components(
+        e,
+        Entity::new()
+            .with(in_chunk(), chunk)
+            .with(chunk_tile_index(), min_idx)
+            .with(on_tile(), covered[0])
+            .with(large_crop_tiles(), covered)
+            .with(despawn_when_loaded(), old_occupant),
+    );
+}
+
+/// Attaches a crop to the tiles at its coordinates if its chunk is loaded, or
+/// defers it until the chunk loads if not.
+fn attach_or_defer(
+    chunks: &HashMap<IVec2, EntityId>,
+    pending: &mut HashMap<IVec2, Vec<EntityId>>,
+    e: EntityId,
+    coords: IVec2,
+) {
+    let xy = coords.div_euclid(IVec2::splat(CHUNK_SIZE as i32));
+    let loaded = chunks.get(&xy).and_then(|chunk| {
+        let tiles = entity::get_component(*chunk, chunk_tile_refs())?;
+        Some((*chunk, tiles))
+    });
+
+    let Some((chunk, tiles)) = loaded else {
+        pending.entry(xy).or_default().push(e);
+        return;
+    };
+
+    if entity::has_component(e, is_large_crop()) {
+        attach_large_crop(e, coords, chunk, &tiles);
+    } else if entity::has_component(e, is_small_crop()) {
+        attach_crop(e, coords, chunk, &tiles, small_crop_occupant());
+    } else {
+        attach_crop(e, coords, chunk, &tiles, medium_crop_occupant());
+    }
+}
 
 pub fn init_shared() {
     let chunks = init_map(chunk());
 
-    chunks.on_event(
-        spawn_query(coords()).requires(is_medium_crop()),
-        move |chunks, e, coords| {
-            let xy = coords / CHUNK_SIZE as i32;
-            if let Some(chunk) = chunks.get(&xy) {
-                println!("{} at {}", e, coords);
+    // crops whose chunks haven't been loaded yet, by chunk position
+    let pending = Arc::new(Mutex::new(HashMap::<IVec2, Vec<EntityId>>::new()));
+
+    for category in [is_small_crop(), is_medium_crop(), is_large_crop()] {
+        spawn_query(coords()).requires(category).bind({
+            let chunks = chunks.clone();
+            let pending = pending.clone();
+            move |entities| {
+                let chunks = chunks.lock().unwrap();
+                let mut pending = pending.lock().unwrap();
+                for (e, coords) in entities {
+                    attach_or_defer(&chunks, &mut pending, e, coords);
+                }
+            }
+        });
+    }
+
+    spawn_query((chunk(), chunk_tile_refs())).bind(move |entities| {
+        let mut chunks = chunks.lock().unwrap();
+        let mut pending = pending.lock().unwrap();
+        for (chunk, (xy, _tiles)) in entities {
+            // this may run before the chunk map hears about the chunk
+            chunks.insert(xy, chunk);
 
-                let fine = coords - xy * CHUNK_SIZE as i32;
-                let tile_idx = fine.y * CHUNK_SIZE as i32 + fine.x;
+            let Some(crops) = pending.remove(&xy) else {
+                continue;
+            };
 
-                let Ok(tile_idx) = TryInto::<u8>::try_into(tile_idx) else {
-                    eprintln!("tile index {} is out-of-bounds", tile_idx);
-                    return;
+            for e in crops {
+                // the crop may have been despawned while it was waiting
+                let Some(coords) = entity::get_component(e, coords()) else {
+                    continue;
                 };
 
-                let tiles = entity::get_component(*chunk, chunk_tile_refs()).unwrap();
-                let tile = tiles[tile_idx as usize];
+                attach_or_defer(&chunks, &mut pending, e, coords);
+            }
+        }
+    });
+
+    // crops may also be spawned with only the tile that they're on, so look up
+    // their coordinates from the tile. for large crops, this is their first tile
+    for category in [is_small_crop(), is_medium_crop(), is_large_crop()] {
+        spawn_query(on_tile())
+            .requires(category)
+            .excludes(coords())
+            .bind(move |entities| {
+                for (e, tile) in entities {
+                    let Some(chunk_entity) = entity::get_component(tile, in_chunk()) else {
+                        continue;
+                    };
+
+                    let Some(chunk_xy) = entity::get_component(chunk_entity, chunk()) else {
+                        continue;
+                    };
 
-                let old_occupant =
-                    entity::get_component(tile, medium_crop_occupant()).unwrap_or_default();
+                    let Some(tile_idx) = entity::get_component(tile, chunk_tile_index()) else {
+                        continue;
+                    };
 
-                if !old_occupant.is_null() {
-                    entity::despawn_recursive(old_occupant);
+                    let local = ivec2(
+                        tile_idx as i32 % CHUNK_SIZE as i32,
+                        tile_idx as i32 / CHUNK_SIZE as i32,
+                    );
+
+                    let xy = chunk_xy * CHUNK_SIZE as i32 + local;
+                    entity::add_component(e, coords(), xy);
                 }
+            });
+    }
 
-                entity::add_components(
-                    e,
-                    Entity::new()
-                        .with(in_chunk(), *chunk)
-                        .with(chunk_tile_index(), tile_idx)
-                        .with(on_tile(), tile)
-                        .with(despawn_when_loaded(), old_occupant)
-                        .with(age(), 0),
-                );
-
-                entity::add_component(tile, medium_crop_occupant(), e);
+    // despawn crops along with the tiles they're on
+    for (category, occupant) in [
+        (is_small_crop(), small_crop_occupant()),
+        (is_medium_crop(), medium_crop_occupant()),
+    ] {
+        despawn_query(occupant).bind(move |entities| {
+            for (_, occupant) in entities {
+                if !occupant.is_null() {
+                    entity::despawn_recursive(occupant);
+                }
             }
-        },
-    );
+        });
+
+        despawn_query(on_tile())
+            .requires(category)
+            .bind(move |entities| {
+                for (e, tile) in entities {
+                    if entity::get_component(tile, occupant) == Some(e) {
+                        entity::set_component(tile, occupant, EntityId::null());
+                    }
+                }
+            });
+    }
+
+    despawn_query(large_crop_occupant()).bind(move |entities| {
+        for (_, occupant) in entities {
+            if !occupant.is_null() && entity::exists(occupant) {
+                entity::despawn_recursive(occupant);
+            }
+        }
+    });
 
-    despawn_query(on_tile())
-        .requires(is_medium_crop())
+    despawn_query(large_crop_tiles())
+        .requires(is_large_crop())
         .bind(move |entities| {
-            for (e, tile) in entities {
-                if entity::get_component(tile, medium_crop_occupant()) == Some(e) {
-                    entity::set_component(tile, medium_crop_occupant(), EntityId::null());
+            for (e, tiles) in entities {
+                for tile in tiles {
+                    if entity::get_component(tile, large_crop_occupant()) == Some(e) {
+                        entity::set_component(tile, large_crop_occupant(), EntityId::null());
+                        entity::remove_component(tile, blocks_movement());
+                    }
                 }
             }
         });
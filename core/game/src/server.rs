@@ -1,14 +1,116 @@
-use ambient_api::prelude::*;
+use ambient_api::{core::player::components::user_id, prelude::*};
 
+use flowerpot_common::{has_room_for, spawn_instance, CHUNK_SIZE};
 use packages::{
     actions::messages::*,
-    crops::components::{is_medium_crop, medium_crop_occupant, on_tile},
-    items::components::held_ref,
+    crops::components::{
+        is_cover_crop, is_large_crop, is_medium_crop, is_small_crop, large_crop_footprint,
+        large_crop_occupant, medium_crop_occupant, on_tile, small_crop_occupant,
+    },
+    items::{
+        components::{
+            class, container_classes, container_counts, durability, held_ref, inventory_ref,
+            max_durability, max_stack, open_container_ref, quantity,
+        },
+        messages::StoreItems,
+    },
+    map::components::{chunk, chunk_tile_index, east_neighbor, in_chunk, south_neighbor},
     player::components::{left_hand_ref, right_hand_ref},
+    soil::components::{is_tilled, is_watered_today, soil_moisture},
+    storage::components::{chest_coords, chest_occupant, is_chest},
+    terrain::components::is_water,
     things::components::{class_ref, is_class},
-    this::components::{pick_up_item_class, place_medium_crop},
+    this::components::{
+        pick_up_item_class, pick_up_next_stage, place_chest, place_large_crop, place_medium_crop,
+        place_small_crop, tills_soil, waters_soil,
+    },
 };
 
+/// Returns the tiles that a large crop placed on `anchor` would cover, or None
+/// if any of them are missing or in a different chunk.
+fn get_footprint(anchor: EntityId, footprint: UVec2) -> Option<Vec<EntityId>> {
+    let chunk = entity::get_component(anchor, in_chunk())?;
+    let mut tiles = Vec::new();
+    let mut row = anchor;
+    for y in 0..footprint.y {
+        if y > 0 {
+            row = entity::get_component(row, south_neighbor())?;
+        }
+
+        let mut tile = row;
+        for x in 0..footprint.x {
+            if x > 0 {
+                tile = entity::get_component(tile, east_neighbor())?;
+            }
+
+            if entity::get_component(tile, in_chunk()) != Some(chunk) {
+                return None;
+            }
+
+            tiles.push(tile);
+        }
+    }
+
+    Some(tiles)
+}
+
+fn has_occupant(tile: EntityId, occupant: Component<EntityId>) -> bool {
+    !entity::get_component(tile, occupant)
+        .unwrap_or_default()
+        .is_null()
+}
+
+fn is_occupied(tile: EntityId) -> bool {
+    has_occupant(tile, medium_crop_occupant())
+        || has_occupant(tile, large_crop_occupant())
+        || has_occupant(tile, chest_occupant())
+}
+
+/// Gets the world coordinates of a tile.
+fn get_tile_coords(tile: EntityId) -> Option<IVec2> {
+    let chunk_entity = entity::get_component(tile, in_chunk())?;
+    let chunk_xy = entity::get_component(chunk_entity, chunk())?;
+    let tile_idx = entity::get_component(tile, chunk_tile_index())? as i32;
+    let local = ivec2(tile_idx % CHUNK_SIZE as i32, tile_idx / CHUNK_SIZE as i32);
+    Some(chunk_xy * CHUNK_SIZE as i32 + local)
+}
+
+/// Gets the item instance held by a hand and its class. Both are null if the
+/// hand is empty.
+fn get_held(hand: EntityId) -> (EntityId, EntityId) {
+    let held = entity::get_component(hand, held_ref()).unwrap_or_default();
+    (held, entity::get_component(held, class()).unwrap_or_default())
+}
+
+/// Uses up one of the items held by a hand.
+fn consume_held(hand: EntityId) {
+    let (held, _class) = get_held(hand);
+    let count = entity::get_component(held, quantity()).unwrap_or(1);
+    if count > 1 {
+        entity::add_component(held, quantity(), count - 1);
+    } else {
+        entity::add_component(hand, held_ref(), EntityId::null());
+        entity::despawn_recursive(held);
+    }
+}
+
+/// Wears down the tool held by a hand, breaking it once it runs out.
+fn wear_held(hand: EntityId) {
+    let (held, _class) = get_held(hand);
+    match entity::get_component(held, durability()) {
+        Some(uses) if uses > 1 => entity::add_component(held, durability(), uses - 1),
+        Some(_) => consume_held(hand),
+        None => {}
+    }
+}
+
+/// Tells a player's client that their action was rejected.
+fn reject_action(player: EntityId, reason: &str) {
+    if let Some(uid) = entity::get_component(player, user_id()) {
+        ActionRejected::new(reason.to_string()).send_client_targeted_reliable(uid);
+    }
+}
+
 #[main]
 pub fn main() {
     spawn_query(())
@@ -25,6 +127,52 @@ pub fn main() {
             }
         });
 
+    spawn_query(())
+        .requires((is_class(), is_large_crop(), pick_up_item_class()))
+        .bind(move |entities| {
+            for (e, _) in entities {
+                RegisterLargeCropAction::new(
+                    "pick_up".to_string(),
+                    e,
+                    EntityId::null(),
+                    EntityId::null(),
+                )
+                .send_local_broadcast(false);
+            }
+        });
+
+    spawn_query(())
+        .requires((is_class(), is_small_crop(), pick_up_item_class()))
+        .bind(move |entities| {
+            for (e, _) in entities {
+                RegisterSmallCropAction::new(
+                    "pick_up".to_string(),
+                    e,
+                    EntityId::null(),
+                    EntityId::null(),
+                )
+                .send_local_broadcast(false);
+            }
+        });
+
+    spawn_query(())
+        .requires(place_small_crop())
+        .bind(move |entities| {
+            for (e, _) in entities {
+                RegisterTileAction::new("place_small".to_string(), e, EntityId::null())
+                    .send_local_broadcast(false);
+            }
+        });
+
+    spawn_query(())
+        .requires(place_large_crop())
+        .bind(move |entities| {
+            for (e, _) in entities {
+                RegisterTileAction::new("place_large".to_string(), e, EntityId::null())
+                    .send_local_broadcast(false);
+            }
+        });
+
     spawn_query(())
         .requires(place_medium_crop())
         .bind(move |entities| {
@@ -34,6 +182,47 @@ pub fn main() {
             }
         });
 
+    spawn_query(())
+        .requires(place_chest())
+        .bind(move |entities| {
+            for (e, _) in entities {
+                RegisterTileAction::new("place_chest".to_string(), e, EntityId::null())
+                    .send_local_broadcast(false);
+            }
+        });
+
+    spawn_query(())
+        .requires((is_class(), is_chest()))
+        .bind(move |entities| {
+            for (e, _) in entities {
+                RegisterChestAction::new(
+                    "open_chest".to_string(),
+                    e,
+                    EntityId::null(),
+                    EntityId::null(),
+                )
+                .send_local_broadcast(false);
+            }
+        });
+
+    spawn_query(())
+        .requires(tills_soil())
+        .bind(move |entities| {
+            for (e, _) in entities {
+                RegisterTileAction::new("till".to_string(), e, EntityId::null())
+                    .send_local_broadcast(false);
+            }
+        });
+
+    spawn_query(())
+        .requires(waters_soil())
+        .bind(move |entities| {
+            for (e, _) in entities {
+                RegisterTileAction::new("water".to_string(), e, EntityId::null())
+                    .send_local_broadcast(false);
+            }
+        });
+
     OnAction::subscribe(move |source, data| {
         if source.local().is_none() {
             return;
@@ -53,16 +242,67 @@ pub fn main() {
             left_hand_ref()
         };
 
+        // picked items stack onto the held items, and otherwise go into the
+        // inventory when the hand is already full
         let hand = entity::get_component(data.player, hand).unwrap();
-        entity::add_component(hand, held_ref(), item);
+        let (held, held_class) = get_held(hand);
+        let held_count = entity::get_component(held, quantity()).unwrap_or(0);
+        let max = entity::get_component(item, max_stack()).unwrap_or(1);
+        if held.is_null() {
+            let uses = entity::get_component(item, max_durability()).unwrap_or(0);
+            let instance = spawn_instance(class(), quantity(), durability(), item, 1, uses);
+            entity::add_component(hand, held_ref(), instance);
+        } else if held_class == item && held_count < max {
+            entity::add_component(held, quantity(), held_count + 1);
+        } else {
+            let inventory = entity::get_component(data.player, inventory_ref());
+            let has_room = |inventory: &EntityId| {
+                has_room_for(
+                    container_classes(),
+                    container_counts(),
+                    max_stack(),
+                    *inventory,
+                    item,
+                )
+            };
+
+            match inventory.filter(has_room) {
+                Some(inventory) => StoreItems::new(inventory, item, 1).send_local_broadcast(false),
+                None => {
+                    reject_action(data.player, "Your inventory is full.");
+                    return;
+                }
+            }
+        }
 
         let crop = data.target;
         let Some(tile) = entity::get_component(crop, on_tile()) else {
             return;
         };
 
+        let next_stage = entity::get_component(crop, pick_up_next_stage());
+
+        // large crops clear their own tiles when they're despawned
+        let category = if entity::has_component(crop, is_large_crop()) {
+            is_large_crop()
+        } else if entity::has_component(crop, is_small_crop()) {
+            entity::add_component(tile, small_crop_occupant(), EntityId::null());
+            is_small_crop()
+        } else {
+            entity::add_component(tile, medium_crop_occupant(), EntityId::null());
+            is_medium_crop()
+        };
+
         entity::despawn_recursive(crop);
-        entity::add_component(tile, medium_crop_occupant(), EntityId::null());
+
+        // some crops keep growing after they've been picked
+        if let Some(next_stage) = next_stage {
+            Entity::new()
+                .with(category, ())
+                .with(class_ref(), next_stage)
+                .with(on_tile(), tile)
+                .spawn();
+        }
     });
 
     OnAction::subscribe(move |source, data| {
@@ -82,22 +322,256 @@ pub fn main() {
 
         let hand = entity::get_component(data.player, hand).unwrap();
 
-        let Some(item) = entity::get_component(hand, held_ref()) else {
+        let (_held, item) = get_held(hand);
+        let Some(place) = entity::get_component(item, place_medium_crop()) else {
             return;
         };
 
-        let Some(place) = entity::get_component(item, place_medium_crop()) else {
+        let tile = data.target;
+        if entity::has_component(tile, is_water()) {
+            return;
+        }
+
+        if is_occupied(tile) {
+            return;
+        }
+
+        Entity::new()
+            .with(is_medium_crop(), ())
+            .with(class_ref(), place)
+            .with(on_tile(), tile)
+            .spawn();
+
+        consume_held(hand);
+    });
+
+    OnAction::subscribe(move |source, data| {
+        if source.local().is_none() {
+            return;
+        }
+
+        if data.id != "place_large" {
+            return;
+        }
+
+        let hand = if data.right_is_primary {
+            right_hand_ref()
+        } else {
+            left_hand_ref()
+        };
+
+        let hand = entity::get_component(data.player, hand).unwrap();
+
+        let (_held, item) = get_held(hand);
+        let Some(place) = entity::get_component(item, place_large_crop()) else {
+            return;
+        };
+
+        let footprint =
+            entity::get_component(place, large_crop_footprint()).unwrap_or(UVec2::ONE);
+        let Some(tiles) = get_footprint(data.target, footprint) else {
+            return;
+        };
+
+        let is_blocked = |tile: &EntityId| {
+            entity::has_component(*tile, is_water()) || is_occupied(*tile)
+        };
+
+        if tiles.iter().any(is_blocked) {
+            return;
+        }
+
+        Entity::new()
+            .with(is_large_crop(), ())
+            .with(class_ref(), place)
+            .with(on_tile(), data.target)
+            .spawn();
+
+        consume_held(hand);
+    });
+
+    OnAction::subscribe(move |source, data| {
+        if source.local().is_none() {
+            return;
+        }
+
+        if data.id != "place_small" {
+            return;
+        }
+
+        let hand = if data.right_is_primary {
+            right_hand_ref()
+        } else {
+            left_hand_ref()
+        };
+
+        let hand = entity::get_component(data.player, hand).unwrap();
+
+        let (_held, item) = get_held(hand);
+        let Some(place) = entity::get_component(item, place_small_crop()) else {
             return;
         };
 
         let tile = data.target;
-        let crop = Entity::new()
-            .with(is_medium_crop(), ())
+        if entity::has_component(tile, is_water()) {
+            return;
+        }
+
+        if has_occupant(tile, small_crop_occupant()) || has_occupant(tile, large_crop_occupant()) {
+            return;
+        }
+
+        if has_occupant(tile, chest_occupant()) {
+            return;
+        }
+
+        // only cover crops may be planted underneath medium crops
+        let is_cover = entity::has_component(place, is_cover_crop());
+        if !is_cover && has_occupant(tile, medium_crop_occupant()) {
+            return;
+        }
+
+        Entity::new()
+            .with(is_small_crop(), ())
             .with(class_ref(), place)
             .with(on_tile(), tile)
             .spawn();
 
-        entity::add_component(tile, medium_crop_occupant(), crop);
-        entity::add_component(hand, held_ref(), EntityId::null());
+        consume_held(hand);
+    });
+
+    OnAction::subscribe(move |source, data| {
+        if source.local().is_none() {
+            return;
+        }
+
+        if data.id != "till" {
+            return;
+        }
+
+        let hand = if data.right_is_primary {
+            right_hand_ref()
+        } else {
+            left_hand_ref()
+        };
+
+        let hand = entity::get_component(data.player, hand).unwrap();
+
+        let tile = data.target;
+        if entity::has_component(tile, is_water()) || entity::has_component(tile, is_tilled()) {
+            return;
+        }
+
+        if is_occupied(tile) {
+            return;
+        }
+
+        // tilling uproots whatever small crops were growing here
+        if let Some(occupant) = entity::get_component(tile, small_crop_occupant()) {
+            if !occupant.is_null() {
+                entity::add_component(tile, small_crop_occupant(), EntityId::null());
+                entity::despawn_recursive(occupant);
+            }
+        }
+
+        entity::add_component(tile, is_tilled(), ());
+        wear_held(hand);
+    });
+
+    OnAction::subscribe(move |source, data| {
+        if source.local().is_none() {
+            return;
+        }
+
+        if data.id != "water" {
+            return;
+        }
+
+        let hand = if data.right_is_primary {
+            right_hand_ref()
+        } else {
+            left_hand_ref()
+        };
+
+        let hand = entity::get_component(data.player, hand).unwrap();
+
+        let (_held, item) = get_held(hand);
+        let Some(amount) = entity::get_component(item, waters_soil()) else {
+            return;
+        };
+
+        let tile = data.target;
+        if entity::has_component(tile, is_water()) {
+            return;
+        }
+
+        // each tile only soaks up one watering a day
+        if entity::has_component(tile, is_watered_today()) {
+            return;
+        }
+
+        let moisture = entity::get_component(tile, soil_moisture()).unwrap_or(0.0);
+        entity::add_component(tile, soil_moisture(), (moisture + amount).min(1.0));
+        entity::add_component(tile, is_watered_today(), ());
+        wear_held(hand);
+    });
+
+    OnAction::subscribe(move |source, data| {
+        if source.local().is_none() {
+            return;
+        }
+
+        if data.id != "place_chest" {
+            return;
+        }
+
+        let hand = if data.right_is_primary {
+            right_hand_ref()
+        } else {
+            left_hand_ref()
+        };
+
+        let hand = entity::get_component(data.player, hand).unwrap();
+
+        let (_held, item) = get_held(hand);
+        let Some(place) = entity::get_component(item, place_chest()) else {
+            return;
+        };
+
+        let tile = data.target;
+        if entity::has_component(tile, is_water()) || is_occupied(tile) {
+            return;
+        }
+
+        let Some(coords) = get_tile_coords(tile) else {
+            return;
+        };
+
+        // chests are set down on top of whatever small crops were growing here
+        if let Some(occupant) = entity::get_component(tile, small_crop_occupant()) {
+            if !occupant.is_null() {
+                entity::add_component(tile, small_crop_occupant(), EntityId::null());
+                entity::despawn_recursive(occupant);
+            }
+        }
+
+        Entity::new()
+            .with(class_ref(), place)
+            .with(chest_coords(), coords)
+            .spawn();
+
+        consume_held(hand);
+    });
+
+    OnAction::subscribe(move |source, data| {
+        if source.local().is_none() {
+            return;
+        }
+
+        if data.id != "open_chest" {
+            return;
+        }
+
+        entity::add_component(data.player, open_container_ref(), data.target);
     });
 }
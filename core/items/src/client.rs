@@ -1,24 +1,171 @@
 use ambient_api::{
     core::{
-        ecs::components::children, primitives::components::cube, rendering::components::color,
-        transform::components::local_to_parent,
+        app::components::main_scene,
+        ecs::components::children,
+        primitives::components::cube,
+        rendering::components::color,
+        text::{
+            components::{font_size, font_style, text},
+            types::FontStyle,
+        },
+        messages::Frame,
+        transform::{
+            components::{
+                local_to_parent, local_to_world, mesh_to_local, mesh_to_world, rotation, scale,
+                translation,
+            },
+            concepts::make_transformable,
+        },
     },
     prelude::*,
 };
 
+use flowerpot_common::{impl_remote_update, RemoteEntityStore};
 use packages::{
-    player::components::{left_hand_ref, local_player_ref, right_hand_ref},
+    fauna::components::{pitch, yaw},
+    map::components::position,
+    player::components::{left_hand_ref, local_player_ref, reach, right_hand_ref},
+    region_networking::components::remote_entity,
+    terrain::components::altitude,
     things::components::class_ref,
     this::{components::*, messages::*},
 };
 
 mod shared;
 
+/// Where the stack number is drawn relative to a hand, in the hand's space.
+const STACK_LABEL_OFFSET: Vec3 = Vec3::new(0.15, 0.15, 0.0);
+
+/// The scale of the stack number's text.
+const STACK_LABEL_SCALE: f32 = 0.004;
+
+/// The scale of the models of items lying on the ground.
+const MAP_ITEM_SCALE: f32 = 0.3;
+
+/// How far from the middle of the player's view that map items can be
+/// targeted, in tiles.
+const MAP_ITEM_TARGET_RADIUS: f32 = 0.5;
+
+/// The height of the player's eyes above their feet.
+const EYE_HEIGHT: f32 = 1.5;
+
+/// Where the left hand of another player's avatar is, in the avatar's space.
+const REMOTE_LEFT_HAND_OFFSET: Vec3 = Vec3::new(-0.4, -0.4, 1.1);
+
+/// Where the right hand of another player's avatar is, in the avatar's space.
+const REMOTE_RIGHT_HAND_OFFSET: Vec3 = Vec3::new(0.4, -0.4, 1.1);
+
+/// The scale of the items held by other players' avatars.
+const REMOTE_HAND_SCALE: f32 = 0.3;
+
 #[main]
 fn main() {
+    let store = RemoteEntityStore::new(remote_entity());
+
+    store.subscribe_update::<UpdateMapItem>(move |e, data| {
+        // the things package copies the class's model onto new instances
+        if !entity::has_component(e, class_ref()) {
+            entity::add_component(e, class_ref(), data.class);
+        }
+
+        entity::add_components(
+            e,
+            Entity::new()
+                .with(is_map_item(), ())
+                .with(class(), data.class)
+                .with(position(), data.position)
+                .with(quantity(), data.quantity),
+        );
+    });
+
+    store.subscribe_update::<UpdateRemoteHeldItems>(move |e, data| {
+        let left = get_remote_hand(e, left_hand_ref(), REMOTE_LEFT_HAND_OFFSET);
+        let right = get_remote_hand(e, right_hand_ref(), REMOTE_RIGHT_HAND_OFFSET);
+
+        // the quantities of other players' stacks aren't labeled
+        update_held_item(left, data.left, 1);
+        update_held_item(right, data.right, 1);
+    });
+
+    spawn_query((position(), altitude()))
+        .requires(is_map_item())
+        .bind(move |entities| {
+            for (e, (position, altitude)) in entities {
+                entity::add_components(
+                    e,
+                    make_transformable()
+                        .with(translation(), position.extend(altitude))
+                        .with(scale(), Vec3::splat(MAP_ITEM_SCALE))
+                        .with(local_to_world(), Mat4::IDENTITY),
+                );
+            }
+        });
+
+    change_query((position(), altitude()))
+        .track_change((position(), altitude()))
+        .requires(is_map_item())
+        .bind(move |entities| {
+            for (e, (position, altitude)) in entities {
+                entity::add_component(e, translation(), position.extend(altitude));
+            }
+        });
+
     run_async(async_main());
 }
 
+/// Gets a hand of another player's avatar, spawning it if it doesn't exist.
+fn get_remote_hand(avatar: EntityId, hand_ref: Component<EntityId>, offset: Vec3) -> EntityId {
+    if let Some(hand) = entity::get_component(avatar, hand_ref) {
+        return hand;
+    }
+
+    let hand = Entity::new()
+        .with(main_scene(), ())
+        .with(local_to_parent(), Mat4::IDENTITY)
+        .with(local_to_world(), Mat4::IDENTITY)
+        .with(translation(), offset)
+        .with(rotation(), Quat::IDENTITY)
+        .with(scale(), Vec3::splat(REMOTE_HAND_SCALE))
+        .spawn();
+
+    entity::add_child(avatar, hand);
+    entity::add_component(avatar, hand_ref, hand);
+    hand
+}
+
+/// Finds the closest map item along the player's line of sight and in reach.
+fn find_targeted_map_item(
+    player: EntityId,
+    map_items: impl IntoIterator<Item = (EntityId, Vec3)>,
+) -> Option<EntityId> {
+    let feet = entity::get_component(player, position())?;
+    let altitude = entity::get_component(player, altitude())?;
+    let origin = feet.extend(altitude + EYE_HEIGHT);
+    let yaw = entity::get_component(player, yaw())?;
+    let pitch = entity::get_component(player, pitch())?;
+    let direction = Quat::from_rotation_z(yaw) * Quat::from_rotation_x(pitch) * -Vec3::Y;
+    let limit = entity::get_component(player, reach())?;
+
+    let mut closest = None;
+    for (e, target) in map_items {
+        let offset = target - origin;
+        let along = offset.dot(direction);
+        if along < 0.0 || along > limit {
+            continue;
+        }
+
+        if (offset - direction * along).length() > MAP_ITEM_TARGET_RADIUS {
+            continue;
+        }
+
+        if closest.map_or(true, |(_, distance)| along < distance) {
+            closest = Some((e, along));
+        }
+    }
+
+    closest.map(|(e, _)| e)
+}
+
 async fn async_main() {
     let local_player_entity = entity::wait_for_component(entity::resources(), local_player_ref())
         .await
@@ -33,12 +180,57 @@ async fn async_main() {
     };
 
     UpdateHeldItems::subscribe(move |_, data| {
-        update_held_item(left_hand, data.left);
-        update_held_item(right_hand, data.right);
+        update_held_item(left_hand, data.left, data.left_quantity);
+        update_held_item(right_hand, data.right, data.right_quantity);
+    });
+
+    // mirror the server's inventory so that the UI can display it
+    let inventory = Entity::new()
+        .with(container_classes(), vec![])
+        .with(container_counts(), vec![])
+        .spawn();
+
+    entity::add_component(local_player_entity, inventory_ref(), inventory);
+
+    UpdateInventory::subscribe(move |_, data| {
+        entity::add_component(inventory, container_classes(), data.classes);
+        entity::add_component(inventory, container_counts(), data.counts);
+    });
+
+    // mirror the contents of whichever container the player has open
+    let open_container = Entity::new()
+        .with(container_classes(), vec![])
+        .with(container_counts(), vec![])
+        .spawn();
+
+    UpdateOpenContainer::subscribe(move |_, data| {
+        if !data.open {
+            entity::remove_component(local_player_entity, open_container_ref());
+            return;
+        }
+
+        entity::add_component(open_container, container_classes(), data.classes);
+        entity::add_component(open_container, container_counts(), data.counts);
+        entity::add_component(local_player_entity, open_container_ref(), open_container);
+    });
+
+    let map_items = query(translation()).requires(is_map_item()).build();
+    Frame::subscribe(move |_| {
+        let player = local_player_entity;
+        let targeted = find_targeted_map_item(player, map_items.evaluate());
+        let selected = entity::get_component(player, map_item_selection_ref());
+        if targeted == selected {
+            return;
+        }
+
+        match targeted {
+            Some(item) => entity::add_component(player, map_item_selection_ref(), item),
+            None => entity::remove_component(player, map_item_selection_ref()),
+        }
     });
 }
 
-fn update_held_item(hand: EntityId, class: EntityId) {
+fn update_held_item(hand: EntityId, class: EntityId, count: u32) {
     for child in entity::get_component(hand, children()).unwrap_or_default() {
         entity::despawn_recursive(child);
     }
@@ -53,4 +245,30 @@ fn update_held_item(hand: EntityId, class: EntityId) {
         .spawn();
 
     entity::add_child(hand, item);
+
+    // label stacks of more than one item with how many there are
+    if count <= 1 {
+        return;
+    }
+
+    let transform = Mat4::from_translation(STACK_LABEL_OFFSET)
+        * Mat4::from_scale(Vec3::splat(STACK_LABEL_SCALE))
+        * Mat4::from_rotation_x(180_f32.to_radians());
+
+    let label = Entity::new()
+        .with(local_to_parent(), transform)
+        .with(text(), count.to_string())
+        .with(font_size(), 36.0)
+        .with(font_style(), FontStyle::Bold)
+        .with(color(), Vec4::ONE)
+        .with(main_scene(), ())
+        .with(local_to_world(), Mat4::IDENTITY)
+        .with(mesh_to_local(), Mat4::IDENTITY)
+        .with(mesh_to_world(), Mat4::IDENTITY)
+        .spawn();
+
+    entity::add_child(hand, label);
 }
+
+impl_remote_update!(UpdateMapItem);
+impl_remote_update!(UpdateRemoteHeldItems);
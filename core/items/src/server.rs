@@ -1,23 +1,300 @@
 use std::collections::HashSet;
 
-use ambient_api::{core::player::components::user_id, prelude::*};
+use ambient_api::{
+    core::player::components::{is_player, user_id},
+    prelude::*,
+};
 
+use flowerpot_common::spawn_instance;
 use packages::{
+    fauna::components::yaw,
+    map::components::{in_chunk, position},
+    player::components::{left_hand_ref, owner_ref, reach, right_hand_ref},
+    region_networking::{components::players_observing, messages::OnSpawnThing},
     this::{components::*, messages::*},
-    player::components::{left_hand_ref, owner_ref, right_hand_ref},
 };
 
 mod shared;
 
-fn update_player_held(e: EntityId) -> Option<()> {
-    let uid = entity::get_component(e, user_id())?;
+/// The number of slots in a player's inventory.
+pub const INVENTORY_SIZE: usize = 24;
+
+/// The most items of a class that stack in one slot, unless it sets max_stack.
+pub const DEFAULT_MAX_STACK: u32 = 99;
+
+/// The time in seconds that dropped items lie on the ground, unless the
+/// map_item_lifetime resource sets it.
+pub const DEFAULT_MAP_ITEM_LIFETIME: f32 = 300.0;
+
+/// The time in seconds between each countdown of map item lifetimes.
+pub const MAP_ITEM_TICK_INTERVAL: f32 = 5.0;
+
+/// How far in front of a player that their dropped items land, in tiles.
+pub const DROP_DISTANCE: f32 = 1.0;
+
+/// How far past their reach that players can walk before their open container
+/// is closed, so that containers opened at the edge of their reach stay open.
+pub const CONTAINER_REACH_MARGIN: f32 = 1.0;
+
+/// The contents of a container entity's slots.
+#[derive(Clone, Debug)]
+pub struct Container {
+    pub classes: Vec<EntityId>,
+    pub counts: Vec<u32>,
+    pub durabilities: Vec<u32>,
+}
+
+impl Container {
+    pub fn new(size: usize) -> Self {
+        Self {
+            classes: vec![EntityId::null(); size],
+            counts: vec![0; size],
+            durabilities: vec![0; size],
+        }
+    }
+
+    pub fn get(e: EntityId) -> Option<Self> {
+        let classes = entity::get_component(e, container_classes())?;
+        let mut counts = entity::get_component(e, container_counts()).unwrap_or_default();
+        let mut durabilities =
+            entity::get_component(e, container_durabilities()).unwrap_or_default();
+        counts.resize(classes.len(), 0);
+        durabilities.resize(classes.len(), 0);
+
+        Some(Self {
+            classes,
+            counts,
+            durabilities,
+        })
+    }
+
+    pub fn store(&self, e: EntityId) {
+        entity::add_component(e, container_classes(), self.classes.clone());
+        entity::add_component(e, container_counts(), self.counts.clone());
+        entity::add_component(e, container_durabilities(), self.durabilities.clone());
+    }
+
+    /// Empties a slot.
+    pub fn clear(&mut self, slot: usize) {
+        self.classes[slot] = EntityId::null();
+        self.counts[slot] = 0;
+        self.durabilities[slot] = 0;
+    }
+
+    /// Adds items to this container, filling existing stacks of the class
+    /// before empty slots. Returns how many items didn't fit.
+    pub fn insert(&mut self, class: EntityId, mut count: u32, durability: u32) -> u32 {
+        let max = entity::get_component(class, max_stack()).unwrap_or(1);
+
+        for stacking in [true, false] {
+            for slot in 0..self.classes.len() {
+                if count == 0 {
+                    return 0;
+                }
+
+                let matches = if stacking {
+                    self.classes[slot] == class
+                } else {
+                    self.classes[slot].is_null()
+                };
+
+                if !matches || self.counts[slot] >= max {
+                    continue;
+                }
+
+                let moved = count.min(max - self.counts[slot]);
+                self.classes[slot] = class;
+                self.counts[slot] += moved;
+                self.durabilities[slot] = durability;
+                count -= moved;
+            }
+        }
+
+        count
+    }
+
+    /// Moves the stack in one slot onto another, merging stacks of the same
+    /// class as far as they fit and swapping them otherwise.
+    pub fn move_stack(&mut self, from: usize, to: usize) {
+        if from == to || from >= self.classes.len() || to >= self.classes.len() {
+            return;
+        }
+
+        let class = self.classes[from];
+        if class.is_null() || self.classes[to] != class {
+            self.classes.swap(from, to);
+            self.counts.swap(from, to);
+            self.durabilities.swap(from, to);
+            return;
+        }
+
+        let max = entity::get_component(class, max_stack()).unwrap_or(1);
+        let moved = self.counts[from].min(max.saturating_sub(self.counts[to]));
+        self.counts[to] += moved;
+        self.counts[from] -= moved;
+        if self.counts[from] == 0 {
+            self.clear(from);
+        }
+    }
+}
+
+/// Gets the class and quantity of an item instance, or a null class if the
+/// instance is null.
+pub fn get_stack(instance: EntityId) -> (EntityId, u32) {
+    let class = entity::get_component(instance, class()).unwrap_or_default();
+    let count = entity::get_component(instance, quantity()).unwrap_or(0);
+    (class, count)
+}
+
+/// Puts an item instance onto the ground at a position as a map item.
+fn drop_map_item(instance: EntityId, at: Vec2) {
+    let lifetime = entity::get_component(entity::resources(), map_item_lifetime())
+        .unwrap_or(DEFAULT_MAP_ITEM_LIFETIME);
+
+    entity::add_components(
+        instance,
+        Entity::new()
+            .with(is_map_item(), ())
+            .with(position(), at)
+            .with(map_item_lifetime(), lifetime),
+    );
+}
+
+/// Gets the position of a container in the world, or of the player who owns
+/// it if it's an inventory.
+fn get_container_position(container: EntityId) -> Option<Vec2> {
+    entity::get_component(container, position()).or_else(|| {
+        let owner = entity::get_component(container, owner_ref())?;
+        entity::get_component(owner, position())
+    })
+}
+
+/// Gets the stacks held in a player's left and right hands.
+fn get_held_stacks(e: EntityId) -> Option<((EntityId, u32), (EntityId, u32))> {
     let left = entity::get_component(e, left_hand_ref())?;
     let right = entity::get_component(e, right_hand_ref())?;
 
     let left_held = entity::get_component(left, held_ref()).unwrap_or_default();
     let right_held = entity::get_component(right, held_ref()).unwrap_or_default();
-    UpdateHeldItems::new(left_held, right_held).send_client_targeted_reliable(uid);
+    Some((get_stack(left_held), get_stack(right_held)))
+}
+
+fn update_player_held(e: EntityId) -> Option<()> {
+    let uid = entity::get_component(e, user_id())?;
+    let ((left_class, left_quantity), (right_class, right_quantity)) = get_held_stacks(e)?;
+
+    UpdateHeldItems::new(left_class, right_class, left_quantity, right_quantity)
+        .send_client_targeted_reliable(uid);
+
+    Some(())
+}
+
+fn send_remote_held(e: EntityId, uid: String) -> Option<()> {
+    let ((left_class, _), (right_class, _)) = get_held_stacks(e)?;
+    UpdateRemoteHeldItems::new(e, left_class, right_class).send_client_targeted_reliable(uid);
+    Some(())
+}
+
+/// Sends the classes that a player is holding to everyone observing them.
+fn update_remote_held(e: EntityId) -> Option<()> {
+    let chunk = entity::get_component(e, in_chunk())?;
+    for player in entity::get_component(chunk, players_observing()).unwrap_or_default() {
+        // players see their own held items through UpdateHeldItems
+        if player == e {
+            continue;
+        }
 
+        if let Some(uid) = entity::get_component(player, user_id()) {
+            send_remote_held(e, uid);
+        }
+    }
+
+    Some(())
+}
+
+fn update_player_inventory(e: EntityId) -> Option<()> {
+    let uid = entity::get_component(e, user_id())?;
+    let inventory = entity::get_component(e, inventory_ref())?;
+    let container = Container::get(inventory)?;
+    UpdateInventory::new(container.classes, container.counts).send_client_targeted_reliable(uid);
+    Some(())
+}
+
+/// Tests if a container is within a player's reach. Containers without a
+/// position, like inventories, are never in reach.
+fn is_container_in_reach(player: EntityId, container: EntityId) -> bool {
+    let (Some(player_position), Some(container_position)) = (
+        entity::get_component(player, position()),
+        entity::get_component(container, position()),
+    ) else {
+        return false;
+    };
+
+    let limit = entity::get_component(player, reach()).unwrap_or_default();
+    player_position.distance(container_position) <= limit + CONTAINER_REACH_MARGIN
+}
+
+fn update_open_container(player: EntityId) -> Option<()> {
+    let uid = entity::get_component(player, user_id())?;
+    let container = entity::get_component(player, open_container_ref())?;
+    let contents = Container::get(container)?;
+    UpdateOpenContainer::new(true, contents.classes, contents.counts)
+        .send_client_targeted_reliable(uid);
+    Some(())
+}
+
+fn close_container(player: EntityId) {
+    if !entity::has_component(player, open_container_ref()) {
+        return;
+    }
+
+    entity::remove_component(player, open_container_ref());
+    if let Some(uid) = entity::get_component(player, user_id()) {
+        UpdateOpenContainer::new(false, Vec::new(), Vec::new()).send_client_targeted_reliable(uid);
+    }
+}
+
+/// Gets the container that a player has open, closing it if it's out of reach.
+fn get_open_container(player: EntityId) -> Option<EntityId> {
+    let container = entity::get_component(player, open_container_ref())?;
+    if !is_container_in_reach(player, container) {
+        close_container(player);
+        return None;
+    }
+
+    Some(container)
+}
+
+/// Moves as much of the stack in a slot of one container into another as fits.
+fn transfer_slot(from: EntityId, slot: usize, to: EntityId) -> Option<()> {
+    let mut source = Container::get(from)?;
+    let mut destination = Container::get(to)?;
+
+    let slot_class = *source.classes.get(slot)?;
+    if slot_class.is_null() {
+        return None;
+    }
+
+    let count = source.counts[slot];
+    let left_over = destination.insert(slot_class, count, source.durabilities[slot]);
+    if left_over == count {
+        return None;
+    }
+
+    source.counts[slot] = left_over;
+    if left_over == 0 {
+        source.clear(slot);
+    }
+
+    source.store(from);
+    destination.store(to);
+    Some(())
+}
+
+fn send_map_item(e: EntityId, uid: String) -> Option<()> {
+    let (item_class, count) = get_stack(e);
+    let item_position = entity::get_component(e, position())?;
+    UpdateMapItem::new(e, item_class, item_position, count).send_client_targeted_reliable(uid);
     Some(())
 }
 
@@ -33,6 +310,477 @@ fn main() {
 
             for player in dirty_players {
                 update_player_held(player);
+                update_remote_held(player);
+            }
+        });
+
+    // instances don't know who holds them, so look through the hands
+    let hands = query((held_ref(), owner_ref())).build();
+    change_query(quantity())
+        .track_change(quantity())
+        .bind(move |entities| {
+            let changed: HashSet<EntityId> = entities.into_iter().map(|(e, _)| e).collect();
+            let mut dirty_players = HashSet::new();
+            for (_hand, (held, owner)) in hands.evaluate() {
+                if changed.contains(&held) {
+                    dirty_players.insert(owner);
+                }
+            }
+
+            for player in dirty_players {
+                update_player_held(player);
+            }
+        });
+
+    spawn_query(is_item()).bind(move |entities| {
+        for (e, _) in entities {
+            if !entity::has_component(e, max_stack()) {
+                let max = if entity::has_component(e, is_tool()) {
+                    1
+                } else {
+                    DEFAULT_MAX_STACK
+                };
+
+                entity::add_component(e, max_stack(), max);
+            }
+        }
+    });
+
+    spawn_query(is_player()).bind(move |entities| {
+        for (e, _) in entities {
+            let inventory = Entity::new().with(owner_ref(), e).spawn();
+            Container::new(INVENTORY_SIZE).store(inventory);
+            entity::add_component(e, inventory_ref(), inventory);
+        }
+    });
+
+    despawn_query(inventory_ref())
+        .requires(is_player())
+        .bind(move |entities| {
+            for (_e, inventory) in entities {
+                entity::despawn_recursive(inventory);
+            }
+        });
+
+    change_query((container_classes(), container_counts(), owner_ref()))
+        .track_change((container_classes(), container_counts()))
+        .bind(move |entities| {
+            let mut dirty_players = HashSet::new();
+            for (_, (_classes, _counts, owner)) in entities {
+                dirty_players.insert(owner);
+            }
+
+            for player in dirty_players {
+                update_player_inventory(player);
+            }
+        });
+
+    // send new players the inventory that they start with
+    spawn_query((container_classes(), owner_ref())).bind(move |entities| {
+        for (_e, (_classes, owner)) in entities {
+            update_player_inventory(owner);
+        }
+    });
+
+    StoreItems::subscribe(move |source, data| {
+        if source.local().is_none() {
+            return;
+        }
+
+        let Some(mut container) = Container::get(data.container) else {
+            return;
+        };
+
+        let left_over = container.insert(data.class, data.count, 0);
+        container.store(data.container);
+
+        // items that don't fit are dropped next to the container, not lost
+        if left_over > 0 {
+            let Some(at) = get_container_position(data.container) else {
+                eprintln!("{} items did not fit in {}", left_over, data.container);
+                return;
+            };
+
+            let instance = spawn_instance(
+                class(),
+                quantity(),
+                durability(),
+                data.class,
+                left_over,
+                0,
+            );
+
+            drop_map_item(instance, at);
+        }
+    });
+
+    MoveInventoryItems::subscribe(move |source, data| {
+        let Some(player) = source.client_entity_id() else {
+            return;
+        };
+
+        let Some(inventory) = entity::get_component(player, inventory_ref()) else {
+            return;
+        };
+
+        let Some(mut container) = Container::get(inventory) else {
+            return;
+        };
+
+        container.move_stack(data.from as usize, data.to as usize);
+        container.store(inventory);
+    });
+
+    HoldInventoryItem::subscribe(move |source, data| {
+        let Some(player) = source.client_entity_id() else {
+            return;
+        };
+
+        let hand = if data.right {
+            right_hand_ref()
+        } else {
+            left_hand_ref()
+        };
+
+        let Some(hand) = entity::get_component(player, hand) else {
+            return;
+        };
+
+        let Some(inventory) = entity::get_component(player, inventory_ref()) else {
+            return;
+        };
+
+        let Some(mut container) = Container::get(inventory) else {
+            return;
+        };
+
+        let slot = data.slot as usize;
+        let Some(slot_class) = container.classes.get(slot).copied() else {
+            return;
+        };
+
+        let held = entity::get_component(hand, held_ref()).unwrap_or_default();
+        let (held_class, held_count) = get_stack(held);
+
+        if held.is_null() && slot_class.is_null() {
+            return;
+        }
+
+        // top up the held stack from the slot
+        if held_class == slot_class {
+            let max = entity::get_component(held_class, max_stack()).unwrap_or(1);
+            let moved = container.counts[slot].min(max.saturating_sub(held_count));
+            entity::add_component(held, quantity(), held_count + moved);
+            container.counts[slot] -= moved;
+            if container.counts[slot] == 0 {
+                container.clear(slot);
+            }
+
+            container.store(inventory);
+            return;
+        }
+
+        let new_held = if slot_class.is_null() {
+            EntityId::null()
+        } else {
+            let count = container.counts[slot];
+            spawn_instance(
+                class(),
+                quantity(),
+                durability(),
+                slot_class,
+                count,
+                container.durabilities[slot],
+            )
+        };
+
+        container.clear(slot);
+        if !held.is_null() {
+            container.classes[slot] = held_class;
+            container.counts[slot] = held_count;
+            container.durabilities[slot] = entity::get_component(held, durability()).unwrap_or(0);
+            entity::despawn_recursive(held);
+        }
+
+        container.store(inventory);
+        entity::add_component(hand, held_ref(), new_held);
+    });
+
+    // another package may have already configured the lifetime of dropped items
+    if !entity::has_component(entity::resources(), map_item_lifetime()) {
+        entity::add_component(
+            entity::resources(),
+            map_item_lifetime(),
+            DEFAULT_MAP_ITEM_LIFETIME,
+        );
+    }
+
+    DropHeldItem::subscribe(move |source, data| {
+        let Some(player) = source.client_entity_id() else {
+            return;
+        };
+
+        let hand = if data.right {
+            right_hand_ref()
+        } else {
+            left_hand_ref()
+        };
+
+        let Some(hand) = entity::get_component(player, hand) else {
+            return;
+        };
+
+        let held = entity::get_component(hand, held_ref()).unwrap_or_default();
+        if held.is_null() {
+            return;
+        }
+
+        let Some(player_position) = entity::get_component(player, position()) else {
+            return;
+        };
+
+        let player_yaw = entity::get_component(player, yaw()).unwrap_or_default();
+        let forward = (Quat::from_rotation_z(player_yaw) * -Vec3::Y).truncate();
+
+        // the held instance itself goes onto the ground
+        entity::add_component(hand, held_ref(), EntityId::null());
+        drop_map_item(held, player_position + forward * DROP_DISTANCE);
+    });
+
+    PickUpMapItem::subscribe(move |source, data| {
+        let Some(player) = source.client_entity_id() else {
+            return;
+        };
+
+        let item = data.thing;
+        if !entity::has_component(item, is_map_item()) {
+            return;
+        }
+
+        let (Some(player_position), Some(item_position)) = (
+            entity::get_component(player, position()),
+            entity::get_component(item, position()),
+        ) else {
+            return;
+        };
+
+        let limit = entity::get_component(player, reach()).unwrap_or_default();
+        if player_position.distance(item_position) > limit {
+            return;
+        }
+
+        let (item_class, mut count) = get_stack(item);
+        let item_durability = entity::get_component(item, durability()).unwrap_or(0);
+
+        // fill the right hand first
+        if let Some(hand) = entity::get_component(player, right_hand_ref()) {
+            let held = entity::get_component(hand, held_ref()).unwrap_or_default();
+            let (held_class, held_count) = get_stack(held);
+            if held.is_null() {
+                let instance = spawn_instance(
+                    class(),
+                    quantity(),
+                    durability(),
+                    item_class,
+                    count,
+                    item_durability,
+                );
+                entity::add_component(hand, held_ref(), instance);
+                count = 0;
+            } else if held_class == item_class {
+                let max = entity::get_component(held_class, max_stack()).unwrap_or(1);
+                let moved = count.min(max.saturating_sub(held_count));
+                if moved > 0 {
+                    entity::add_component(held, quantity(), held_count + moved);
+                    count -= moved;
+                }
+            }
+        }
+
+        if count > 0 {
+            let inventory = entity::get_component(player, inventory_ref()).unwrap_or_default();
+            if let Some(mut container) = Container::get(inventory) {
+                count = container.insert(item_class, count, item_durability);
+                container.store(inventory);
+            }
+        }
+
+        // whatever didn't fit stays on the ground
+        if count == 0 {
+            entity::despawn_recursive(item);
+        } else {
+            entity::set_component(item, quantity(), count);
+        }
+    });
+
+    let map_items = query(map_item_lifetime()).requires(is_map_item()).build();
+    run_async(async move {
+        loop {
+            sleep(MAP_ITEM_TICK_INTERVAL).await;
+
+            for (e, time_left) in map_items.evaluate() {
+                let time_left = time_left - MAP_ITEM_TICK_INTERVAL;
+                if time_left > 0.0 {
+                    entity::set_component(e, map_item_lifetime(), time_left);
+                } else {
+                    entity::despawn_recursive(e);
+                }
+            }
+        }
+    });
+
+    change_query((in_chunk(), quantity()))
+        .track_change(quantity())
+        .requires(is_map_item())
+        .bind(move |entities| {
+            for (e, (chunk, _quantity)) in entities {
+                let observers = entity::get_component(chunk, players_observing());
+                for player in observers.unwrap_or_default() {
+                    if let Some(uid) = entity::get_component(player, user_id()) {
+                        send_map_item(e, uid);
+                    }
+                }
+            }
+        });
+
+    OnSpawnThing::subscribe(move |source, spawn| {
+        if source.local().is_none() {
+            return;
+        }
+
+        if entity::has_component(spawn.thing, is_map_item()) {
+            send_map_item(spawn.thing, spawn.player_uid);
+        } else if entity::has_component(spawn.thing, is_player()) {
+            send_remote_held(spawn.thing, spawn.player_uid);
+        }
+    });
+
+    spawn_query(open_container_ref())
+        .requires(is_player())
+        .bind(move |entities| {
+            for (player, _container) in entities {
+                update_open_container(player);
+            }
+        });
+
+    change_query(open_container_ref())
+        .track_change(open_container_ref())
+        .requires(is_player())
+        .bind(move |entities| {
+            for (player, _container) in entities {
+                update_open_container(player);
+            }
+        });
+
+    // keep everyone looking into a container up-to-date with its contents
+    let viewers = query(open_container_ref()).requires(is_player()).build();
+    change_query((container_classes(), container_counts()))
+        .track_change((container_classes(), container_counts()))
+        .bind(move |entities| {
+            let changed: HashSet<EntityId> = entities.into_iter().map(|(e, _)| e).collect();
+            for (player, container) in viewers.evaluate() {
+                if changed.contains(&container) {
+                    update_open_container(player);
+                }
+            }
+        });
+
+    let viewers = query(open_container_ref()).requires(is_player()).build();
+    despawn_query(container_classes()).bind(move |entities| {
+        let despawned: HashSet<EntityId> = entities.into_iter().map(|(e, _)| e).collect();
+        for (player, container) in viewers.evaluate() {
+            if despawned.contains(&container) {
+                close_container(player);
+            }
+        }
+    });
+
+    change_query((open_container_ref(), position()))
+        .track_change(position())
+        .requires(is_player())
+        .bind(move |entities| {
+            for (player, (container, _position)) in entities {
+                if !is_container_in_reach(player, container) {
+                    close_container(player);
+                }
             }
         });
+
+    CloseContainer::subscribe(move |source, _data| {
+        if let Some(player) = source.client_entity_id() {
+            close_container(player);
+        }
+    });
+
+    TakeContainerItems::subscribe(move |source, data| {
+        let Some(player) = source.client_entity_id() else {
+            return;
+        };
+
+        let Some(container) = get_open_container(player) else {
+            return;
+        };
+
+        if let Some(inventory) = entity::get_component(player, inventory_ref()) {
+            transfer_slot(container, data.slot as usize, inventory);
+        }
+    });
+
+    StoreInventoryItems::subscribe(move |source, data| {
+        let Some(player) = source.client_entity_id() else {
+            return;
+        };
+
+        let Some(container) = get_open_container(player) else {
+            return;
+        };
+
+        if let Some(inventory) = entity::get_component(player, inventory_ref()) {
+            transfer_slot(inventory, data.slot as usize, container);
+        }
+    });
+
+    StoreHeldItems::subscribe(move |source, data| {
+        let Some(player) = source.client_entity_id() else {
+            return;
+        };
+
+        let Some(container) = get_open_container(player) else {
+            return;
+        };
+
+        let hand = if data.right {
+            right_hand_ref()
+        } else {
+            left_hand_ref()
+        };
+
+        let Some(hand) = entity::get_component(player, hand) else {
+            return;
+        };
+
+        let held = entity::get_component(hand, held_ref()).unwrap_or_default();
+        let (held_class, held_count) = get_stack(held);
+        if held.is_null() {
+            return;
+        }
+
+        let Some(mut contents) = Container::get(container) else {
+            return;
+        };
+
+        let held_durability = entity::get_component(held, durability()).unwrap_or(0);
+        let left_over = contents.insert(held_class, held_count, held_durability);
+        if left_over == held_count {
+            return;
+        }
+
+        contents.store(container);
+        if left_over == 0 {
+            entity::add_component(hand, held_ref(), EntityId::null());
+            entity::despawn_recursive(held);
+        } else {
+            entity::set_component(held, quantity(), left_over);
+        }
+    });
 }
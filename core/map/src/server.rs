@@ -1,74 +1,223 @@
-use std::collections::HashMap;
-
 use ambient_api::{core::network::components::no_sync, prelude::*};
-use flowerpot_common::CHUNK_SIZE;
+use flowerpot_common::{PositionMap, CHUNK_SIZE};
 
 use packages::{
     region_networking::components::{in_region, players_observing},
-    this::components::*,
+    this::{components::*, messages::*},
 };
 
 mod shared;
 
-pub fn stitch_neighbors(entities: HashMap<IVec2, EntityId>) {
-    for (pos, e) in entities.iter() {
-        let pos = *pos;
-        let e = *e;
+/// The time in seconds that a chunk must go unobserved before it is unloaded.
+pub const CHUNK_UNLOAD_DELAY: f32 = 10.0;
+
+/// The time in seconds between broadcasting OnChunkUnload and despawning the
+/// chunk, giving other packages a chance to persist the chunk's state.
+pub const CHUNK_UNLOAD_GRACE: f32 = 1.0;
+
+const NEIGHBOR_OFFSETS: [IVec2; 4] = [IVec2::X, IVec2::Y, IVec2::NEG_X, IVec2::NEG_Y];
+
+/// Links two neighboring entities together. `b` must be at `a`'s position plus `offset`.
+pub fn link_neighbors(a: EntityId, b: EntityId, offset: IVec2) {
+    let (forward, backward) = neighbor_components(offset);
+    entity::add_component(a, forward, b);
+    entity::add_component(b, backward, a);
+}
 
-        if let Some(n) = entities.get(&(pos + IVec2::X)).copied() {
-            entity::add_component(e, east_neighbor(), n);
-            entity::add_component(n, west_neighbor(), e);
+/// Removes the neighbor link to `a` from the entity at `a`'s position plus `offset`.
+pub fn unlink_neighbor(a: EntityId, offset: IVec2) {
+    let (forward, backward) = neighbor_components(offset);
+    if let Some(b) = entity::get_component(a, forward) {
+        if entity::exists(b) {
+            entity::remove_component(b, backward);
         }
+    }
+}
+
+/// Returns the components linking an entity to its neighbor at `offset` and back.
+fn neighbor_components(offset: IVec2) -> (Component<EntityId>, Component<EntityId>) {
+    match (offset.x, offset.y) {
+        (1, 0) => (east_neighbor(), west_neighbor()),
+        (-1, 0) => (west_neighbor(), east_neighbor()),
+        (0, 1) => (south_neighbor(), north_neighbor()),
+        (0, -1) => (north_neighbor(), south_neighbor()),
+        _ => panic!("{} is not a neighbor offset", offset),
+    }
+}
+
+/// Returns the local positions of the tiles along a chunk's edge facing `offset`.
+fn edge_tiles(offset: IVec2) -> impl Iterator<Item = IVec2> {
+    let last = CHUNK_SIZE as i32 - 1;
+    (0..CHUNK_SIZE as i32).map(move |i| match (offset.x, offset.y) {
+        (1, 0) => ivec2(last, i),
+        (-1, 0) => ivec2(0, i),
+        (0, 1) => ivec2(i, last),
+        _ => ivec2(i, 0),
+    })
+}
+
+fn tile_index(local: IVec2) -> usize {
+    local.y as usize * CHUNK_SIZE + local.x as usize
+}
+
+/// Spawns the tiles of a newly-requested chunk and stitches them into the
+/// already-loaded chunks around it.
+pub fn generate_chunk(chunks: &PositionMap, chunk_entity: EntityId, position: IVec2) {
+    let tile_num = CHUNK_SIZE * CHUNK_SIZE;
+    let mut tiles = Vec::with_capacity(tile_num);
+    for tile_idx in 0..tile_num {
+        let tile = Entity::new()
+            .with(in_chunk(), chunk_entity)
+            .with(chunk_tile_index(), tile_idx as u8)
+            .with(no_sync(), ())
+            .spawn();
+
+        tiles.push(tile);
+    }
+
+    for y in 0..CHUNK_SIZE as i32 {
+        for x in 0..CHUNK_SIZE as i32 {
+            let local = ivec2(x, y);
+            let tile = tiles[tile_index(local)];
+
+            if x + 1 < CHUNK_SIZE as i32 {
+                link_neighbors(tile, tiles[tile_index(local + IVec2::X)], IVec2::X);
+            }
+
+            if y + 1 < CHUNK_SIZE as i32 {
+                link_neighbors(tile, tiles[tile_index(local + IVec2::Y)], IVec2::Y);
+            }
+        }
+    }
+
+    let chunks = chunks.lock().unwrap();
+    for offset in NEIGHBOR_OFFSETS {
+        let Some(neighbor) = chunks.get(&(position + offset)).copied() else {
+            continue;
+        };
+
+        // skip neighbors that haven't been generated yet; they'll stitch to us
+        let Some(neighbor_tiles) = entity::get_component(neighbor, chunk_tile_refs()) else {
+            continue;
+        };
+
+        link_neighbors(chunk_entity, neighbor, offset);
+
+        let chunk_size = IVec2::splat(CHUNK_SIZE as i32);
+        for local in edge_tiles(offset) {
+            let other = (local + offset).rem_euclid(chunk_size);
+            link_neighbors(
+                tiles[tile_index(local)],
+                neighbor_tiles[tile_index(other)],
+                offset,
+            );
+        }
+    }
+
+    entity::add_component(chunk_entity, chunk_tile_refs(), tiles);
+}
+
+/// Despawns a chunk and its tiles, unlinking them from the neighboring chunks.
+pub fn unload_chunk(chunk_entity: EntityId) {
+    let tiles = entity::get_component(chunk_entity, chunk_tile_refs()).unwrap_or_default();
 
-        if let Some(n) = entities.get(&(pos + IVec2::Y)).copied() {
-            entity::add_component(e, south_neighbor(), n);
-            entity::add_component(n, north_neighbor(), e);
+    if tiles.len() == CHUNK_SIZE * CHUNK_SIZE {
+        for offset in NEIGHBOR_OFFSETS {
+            for local in edge_tiles(offset) {
+                unlink_neighbor(tiles[tile_index(local)], offset);
+            }
         }
     }
+
+    for offset in NEIGHBOR_OFFSETS {
+        unlink_neighbor(chunk_entity, offset);
+    }
+
+    for tile in tiles {
+        entity::despawn_recursive(tile);
+    }
+
+    entity::despawn_recursive(chunk_entity);
+}
+
+fn is_unobserved(chunk_entity: EntityId) -> bool {
+    entity::exists(chunk_entity)
+        && entity::get_component(chunk_entity, players_observing())
+            .unwrap_or_default()
+            .is_empty()
+}
+
+/// Tests if a chunk is still unobserved since the unload of the given generation
+/// was scheduled.
+fn is_unobserved_since(chunk_entity: EntityId, generation: u32) -> bool {
+    is_unobserved(chunk_entity)
+        && entity::get_component(chunk_entity, chunk_unload_generation()) == Some(generation)
+}
+
+/// Unloads a chunk once it has gone unobserved for long enough.
+fn schedule_unload(chunk_entity: EntityId, position: IVec2) {
+    // cancel any unloads scheduled before the chunk was last observed
+    let generation = entity::mutate_component_with_default(
+        chunk_entity,
+        chunk_unload_generation(),
+        0,
+        |generation| *generation = generation.wrapping_add(1),
+    );
+
+    run_async(async move {
+        sleep(CHUNK_UNLOAD_DELAY).await;
+        if !is_unobserved_since(chunk_entity, generation) {
+            return;
+        }
+
+        OnChunkUnload::new(chunk_entity, position).send_local_broadcast(true);
+        sleep(CHUNK_UNLOAD_GRACE).await;
+        if !is_unobserved_since(chunk_entity, generation) {
+            return;
+        }
+
+        unload_chunk(chunk_entity);
+    });
 }
 
 #[main]
 pub fn main() {
-    shared::init_shared_map();
-
-    let mut chunks = HashMap::new();
-    let mut tiles = HashMap::new();
-    for x in -4..=4 {
-        for y in -4..=4 {
-            let position = IVec2::new(x, y);
-            let chunk = Entity::new()
-                .with(chunk(), position)
-                .with(no_sync(), ())
-                .with(players_observing(), vec![])
-                .spawn();
-
-            let tile_num = CHUNK_SIZE * CHUNK_SIZE;
-            let mut chunk_tiles = Vec::with_capacity(tile_num);
-            let mut tile_idx = 0;
-            for ty in 0..CHUNK_SIZE {
-                for tx in 0..CHUNK_SIZE {
-                    let tile = Entity::new()
-                        .with(in_chunk(), chunk)
-                        .with(chunk_tile_index(), tile_idx)
-                        .with(no_sync(), ())
-                        .spawn();
-
-                    chunk_tiles.push(tile);
-                    let position = position * CHUNK_SIZE as i32 + ivec2(tx as i32, ty as i32);
-                    tiles.insert(position, tile);
-                    tile_idx += 1;
+    let chunks = shared::init_shared_map();
+
+    // chunks are spawned on-demand by other packages with only a chunk
+    // component, so fill out the rest of the chunk here
+    spawn_query(chunk()).excludes(chunk_tile_refs()).bind({
+        let chunks = chunks.clone();
+        move |entities| {
+            for (e, position) in entities {
+                chunks.lock().unwrap().insert(position, e);
+
+                entity::add_component(e, no_sync(), ());
+
+                // a player may have already started observing this chunk
+                if !entity::has_component(e, players_observing()) {
+                    entity::add_component(e, players_observing(), vec![]);
                 }
-            }
 
-            entity::add_component(chunk, chunk_tile_refs(), chunk_tiles);
+                generate_chunk(&chunks, e, position);
 
-            chunks.insert(position, chunk);
+                // chunks spawned by gameplay may never be observed at all
+                if is_unobserved(e) {
+                    schedule_unload(e, position);
+                }
+            }
         }
-    }
+    });
 
-    // TODO faster stitching inside of the loop
-    stitch_neighbors(chunks);
-    stitch_neighbors(tiles);
+    change_query((chunk(), players_observing()))
+        .track_change(players_observing())
+        .bind(move |entities| {
+            for (e, (position, observing)) in entities {
+                if observing.is_empty() {
+                    schedule_unload(e, position);
+                }
+            }
+        });
 
     spawn_query(in_chunk()).bind(move |entities| {
         for (e, chunk) in entities {
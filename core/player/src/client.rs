@@ -84,7 +84,7 @@ fn main() {
                     .with_merge(make_transformable())
                     .with(local_to_world(), Mat4::IDENTITY)
                     .with(position(), Vec2::ZERO)
-                    .with(speed(), 10.0) // TODO terrain-based speed
+                    .with(speed(), BASE_SPEED)
                     .with(head_ref(), head),
             );
 
@@ -116,7 +116,9 @@ fn main() {
             }
         });
 
-    let input = Arc::new(Mutex::new(InputPrediction::new()));
+    let chunks = flowerpot_common::init_map(chunk());
+    let walkable = WalkableMap::new(chunks.clone());
+    let input = Arc::new(Mutex::new(InputPrediction::new(walkable)));
 
     Frame::subscribe({
         let input = input.clone();
@@ -137,10 +139,10 @@ fn main() {
     query((yaw(), pitch(), position(), altitude()))
         .requires(is_player())
         .each_frame(move |players| {
-            for (_e, (yaw, pitch, position, altitude)) in players {
+            for (e, (yaw, pitch, position, altitude)) in players {
                 let origin = position.extend(altitude + 1.5); // TODO player height component
                 let delta = Quat::from_rotation_z(yaw) * Quat::from_rotation_x(pitch) * -Vec3::Y;
-                let limit = 10.0; // TODO player reach
+                let limit = entity::get_component(e, reach()).unwrap_or(DEFAULT_REACH);
 
                 RaycastRequest::new(origin, delta, limit).send_local_broadcast(false);
             }
@@ -183,7 +185,6 @@ fn main() {
         entity::add_component(player::get_local(), tile_selection_ref(), highlight);
     });
 
-    let chunks = flowerpot_common::init_map(chunk());
     let mut sequence = 0;
     chunks.on_message(move |chunks, _, data: UpdateLoadedChunks| {
         if data.sequence < sequence {
@@ -235,19 +236,21 @@ pub struct InputPrediction {
     inputs: VecDeque<InputStep>,
     last_server_update: u64,
     state: PlayerState,
+    walkable: WalkableMap,
     e: EntityId,
 }
 
 impl InputPrediction {
-    pub fn new() -> Self {
+    pub fn new(walkable: WalkableMap) -> Self {
         Self {
             local_sequence: 0,
             inputs: VecDeque::new(),
             last_server_update: 0,
             state: PlayerState {
                 position: Vec2::new(0.0, 0.0),
-                speed: 1.0,
+                speed: BASE_SPEED,
             },
+            walkable,
             e: player::get_local(),
         }
     }
@@ -275,7 +278,7 @@ impl InputPrediction {
         }
         .send_server_unreliable();
 
-        self.state.apply(&input.state, input.dt);
+        self.state.apply(&input.state, input.dt, &self.walkable);
         self.state.set(self.e);
         self.inputs.push_front(input);
         self.local_sequence += 1;
@@ -298,7 +301,7 @@ impl InputPrediction {
 
         // rewind
         for input in self.inputs.iter().rev() {
-            state.apply(&input.state, input.dt);
+            state.apply(&input.state, input.dt, &self.walkable);
         }
 
         // apply new state
@@ -1,9 +1,11 @@
+use std::collections::HashMap;
+
 use ambient_api::{
     core::player::components::{is_player, user_id},
     prelude::*,
 };
 
-use flowerpot_common::SystemExt;
+use flowerpot_common::{SystemExt, CHUNK_SIZE};
 use packages::{
     fauna::components::{is_fauna, yaw},
     map::components::{chunk, in_chunk, position},
@@ -15,27 +17,44 @@ use shared::*;
 
 mod shared;
 
+/// Returns the chunk at the given position, requesting it from the map package
+/// if it hasn't been loaded yet.
+fn get_or_spawn_chunk(chunks: &mut HashMap<IVec2, EntityId>, position: IVec2) -> EntityId {
+    *chunks
+        .entry(position)
+        .or_insert_with(|| Entity::new().with(chunk(), position).spawn())
+}
+
 #[main]
 fn main() {
     let player_class = Entity::new()
         .with(is_class(), ())
         .with(is_fauna(), ())
-        .with(speed(), 30.0)
+        .with(speed(), BASE_SPEED)
+        .with(reach(), DEFAULT_REACH)
         .with(model_prefab_url(), url("player.glb"))
         .spawn();
 
     let chunks = flowerpot_common::init_map(chunk());
+    let walkable = WalkableMap::new(chunks.clone());
 
-    spawn_query((is_player(), is_fauna())).bind(move |entities| {
-        for (e, _) in entities {
+    chunks.on_event(
+        spawn_query((is_player(), is_fauna())),
+        move |chunks, e, _| {
             let left_hand = Entity::new().with(owner_ref(), e).spawn();
             let right_hand = Entity::new().with(owner_ref(), e).spawn();
 
+            // make sure the spawn chunk exists so that the player gets an
+            // in_chunk component and their surrounding chunks start loading
+            let spawn_position = vec2(0.0, 0.0);
+            let spawn_chunk = (spawn_position / CHUNK_SIZE as f32).floor().as_ivec2();
+            get_or_spawn_chunk(chunks, spawn_chunk);
+
             entity::add_components(
                 e,
                 Entity::new()
                     .with(class_ref(), player_class)
-                    .with(position(), vec2(0.0, 0.0))
+                    .with(position(), spawn_position)
                     .with(direction(), vec2(0.0, 0.0))
                     .with(yaw(), 0.0)
                     .with(left_hand_ref(), left_hand)
@@ -44,8 +63,8 @@ fn main() {
                     .with(chunk_sequence(), 1)
                     .with(input_sequence(), 0),
             );
-        }
-    });
+        },
+    );
 
     chunks.on_event(
         despawn_query((user_id(), loaded_chunks())).requires(is_player()),
@@ -78,11 +97,8 @@ fn main() {
 
             for new in new_chunks.iter() {
                 if !old_chunks.contains(new) {
-                    let Some(chunk) = chunks.get(new) else {
-                        continue;
-                    };
-
-                    LoadPlayerRegion::new(*chunk, e, uid.clone()).send_local_broadcast(false);
+                    let chunk = get_or_spawn_chunk(chunks, *new);
+                    LoadPlayerRegion::new(chunk, e, uid.clone()).send_local_broadcast(false);
                 }
             }
 
@@ -135,7 +151,7 @@ fn main() {
         .each_frame(move |entities| {
             let dt = delta_time();
             for (e, (sequence, uid)) in entities {
-                let Some(state) = update_player(e, dt) else {
+                let Some(state) = update_player(e, dt, &walkable) else {
                     continue;
                 };
 
@@ -1,11 +1,143 @@
 use ambient_api::prelude::*;
+use flowerpot_common::{PositionMap, CHUNK_SIZE};
 
 use crate::packages::{
+    crops::components::{large_crop_occupant, medium_crop_occupant},
     fauna::components::{pitch, yaw},
-    map::components::position,
+    map::components::{blocks_movement, chunk_tile_refs, position, speed_multiplier},
+    soil::components::is_tilled,
+    terrain::components::{heightmap, terrain_height_quantization, water_depth},
     this::components::*,
 };
 
+/// The speed of players walking on unmodified terrain, in tiles per second.
+pub const BASE_SPEED: f32 = 10.0;
+
+/// The default reach of players, in tiles.
+pub const DEFAULT_REACH: f32 = 10.0;
+
+/// The maximum difference in altitude between the corners of a walkable tile.
+pub const MAX_WALKABLE_SLOPE: f32 = 1.0;
+
+/// The deepest water that players can wade through.
+pub const MAX_WADING_DEPTH: f32 = 0.5;
+
+/// The speed multiplier for wading through shallow water.
+pub const WADING_SPEED_MULTIPLIER: f32 = 0.5;
+
+/// The speed multiplier for walking over loose, tilled soil.
+pub const TILLED_SOIL_SPEED_MULTIPLIER: f32 = 0.8;
+
+/// Looks up tiles on the map to find out where players are allowed to walk.
+///
+/// Shared by client-side prediction and the server so that both agree on
+/// where players end up.
+#[derive(Clone)]
+pub struct WalkableMap {
+    chunks: PositionMap,
+}
+
+impl WalkableMap {
+    pub fn new(chunks: PositionMap) -> Self {
+        Self { chunks }
+    }
+
+    /// Looks up the chunk entity, the tile entity, and the local position of
+    /// the tile in its chunk at the given map coordinates.
+    fn get_tile(&self, tile_xy: IVec2) -> Option<(EntityId, Option<EntityId>, IVec2)> {
+        let chunk_size = IVec2::splat(CHUNK_SIZE as i32);
+        let chunk_xy = tile_xy.div_euclid(chunk_size);
+        let local = tile_xy.rem_euclid(chunk_size);
+
+        let chunk = self.chunks.lock().unwrap().get(&chunk_xy).copied()?;
+
+        let tile_idx = local.y as usize * CHUNK_SIZE + local.x as usize;
+        let tile = entity::get_component(chunk, chunk_tile_refs())
+            .and_then(|tiles| tiles.get(tile_idx).copied());
+
+        Some((chunk, tile, local))
+    }
+
+    /// Tests if the tile at the given map coordinates can be walked on.
+    ///
+    /// Tiles in chunks that haven't been loaded yet are never walkable.
+    pub fn is_walkable(&self, tile_xy: IVec2) -> bool {
+        let Some((chunk, tile, local)) = self.get_tile(tile_xy) else {
+            return false;
+        };
+
+        let Some(altitudes) = entity::get_component(chunk, heightmap()) else {
+            return false;
+        };
+
+        if let Some(tile) = tile {
+            if entity::has_component(tile, blocks_movement()) {
+                return false;
+            }
+
+            if entity::get_component(tile, water_depth()).unwrap_or(0.0) > MAX_WADING_DEPTH {
+                return false;
+            }
+        }
+
+        let dim = CHUNK_SIZE + 1;
+        let (x, y) = (local.x as usize, local.y as usize);
+        let corners = [(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)]
+            .map(|(x, y)| altitudes[y * dim + x]);
+
+        let min = corners.iter().min().unwrap();
+        let max = corners.iter().max().unwrap();
+        let quantization =
+            entity::get_component(entity::synchronized_resources(), terrain_height_quantization())
+                .unwrap_or(1.0);
+
+        (max - min) as f32 / quantization <= MAX_WALKABLE_SLOPE
+    }
+
+    /// Returns the multiplier of the speed of players walking on the tile at
+    /// the given map coordinates.
+    ///
+    /// Only uses tile state that's synced to clients: the terrain's water, the
+    /// soil's tilling, the tile's own speed multiplier, such as a road's, and
+    /// the classes of the crops occupying the tile.
+    pub fn speed_multiplier(&self, tile_xy: IVec2) -> f32 {
+        let Some((_chunk, Some(tile), _local)) = self.get_tile(tile_xy) else {
+            return 1.0;
+        };
+
+        let mut multiplier = 1.0;
+
+        if entity::has_component(tile, water_depth()) {
+            multiplier *= WADING_SPEED_MULTIPLIER;
+        }
+
+        if entity::has_component(tile, is_tilled()) {
+            multiplier *= TILLED_SOIL_SPEED_MULTIPLIER;
+        }
+
+        multiplier *= entity::get_component(tile, speed_multiplier()).unwrap_or(1.0);
+
+        for layer in [medium_crop_occupant(), large_crop_occupant()] {
+            let occupant = entity::get_component(tile, layer).unwrap_or_default();
+            if !occupant.is_null() {
+                multiplier *= entity::get_component(occupant, speed_multiplier()).unwrap_or(1.0);
+            }
+        }
+
+        multiplier
+    }
+
+    /// Tests if a player can move between two positions.
+    ///
+    /// Players that are already on unwalkable tiles may always move so that
+    /// they can't get stuck.
+    pub fn can_move(&self, from: Vec2, to: Vec2) -> bool {
+        let from = from.floor().as_ivec2();
+        let to = to.floor().as_ivec2();
+        from == to || self.is_walkable(to) || !self.is_walkable(from)
+    }
+}
+
 // TODO this should be a concept-as-a-struct
 pub struct PlayerState {
     pub position: Vec2,
@@ -25,10 +157,20 @@ impl PlayerState {
         entity::add_component(e, speed(), self.speed);
     }
 
-    pub fn apply(&mut self, input: &InputState, dt: f32) {
+    pub fn apply(&mut self, input: &InputState, dt: f32, map: &WalkableMap) {
         let rotate = Mat2::from_angle(input.yaw);
-        let delta = rotate * input.direction * self.speed;
-        self.position += delta * dt;
+        let tile = self.position.floor().as_ivec2();
+        let speed = self.speed * map.speed_multiplier(tile);
+        let delta = rotate * input.direction * speed * dt;
+
+        // move along each axis separately so that players slide along the
+        // edges of tiles that they can't walk onto
+        for axis in [Vec2::X, Vec2::Y] {
+            let next = self.position + delta * axis;
+            if map.can_move(self.position, next) {
+                self.position = next;
+            }
+        }
     }
 }
 
@@ -58,10 +200,10 @@ impl InputState {
 /// Utility function to apply movement logic to a single entity.
 ///
 /// Returns `None` if any necessary components were missing.
-pub fn update_player(e: EntityId, dt: f32) -> Option<PlayerState> {
+pub fn update_player(e: EntityId, dt: f32, map: &WalkableMap) -> Option<PlayerState> {
     let mut state = PlayerState::get(e)?;
     let input = InputState::get(e)?;
-    state.apply(&input, dt);
+    state.apply(&input, dt, map);
     state.set(e);
     Some(state)
 }
@@ -0,0 +1,871 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use ambient_api::{
+    core::player::components::{is_player, user_id},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use flowerpot_common::{ActorExt, SystemExt, CHUNK_SIZE};
+use packages::{
+    crops::components::{
+        age, birth_tick, coords, growth_tick, is_large_crop, is_medium_crop, is_small_crop,
+        large_crop_occupant, last_growth_tick, medium_crop_occupant, on_tile,
+        small_crop_occupant,
+    },
+    items::components::{
+        class, container_classes, container_counts, container_durabilities, durability, held_ref,
+        quantity,
+    },
+    map::{
+        components::{chunk, chunk_tile_refs},
+        messages::OnChunkUnload,
+    },
+    player::components::{left_hand_ref, owner_ref, right_hand_ref},
+    soil::components::{is_tilled, soil_fertility, soil_moisture},
+    storage::components::{chest_coords, chest_occupant},
+    terrain::components::*,
+    things::components::{class_id, class_ref, is_class},
+    this::components::*,
+    weather::components::{day, time_of_day},
+};
+
+mod shared;
+
+/// The path to the world save file, relative to the server's working directory.
+pub const SAVE_PATH: &str = "world.json";
+
+/// The version of the save format written by this package.
+///
+/// Bump this whenever the format changes incompatibly.
+pub const SAVE_VERSION: u32 = 2;
+
+/// The default value of the autosave_interval resource.
+pub const DEFAULT_AUTOSAVE_INTERVAL: f32 = 60.0;
+
+/// The root of a world save file.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct WorldSave {
+    pub version: u32,
+    #[serde(default)]
+    pub time_of_day: Option<f64>,
+    /// The season and year are worked out from the day.
+    #[serde(default)]
+    pub day: Option<u32>,
+    /// Worlds saved before scheduled growth start counting from zero.
+    #[serde(default)]
+    pub growth_tick: Option<u32>,
+    #[serde(default)]
+    pub terrain: Option<TerrainSave>,
+    #[serde(default)]
+    pub chunks: Vec<ChunkSave>,
+    #[serde(default)]
+    pub players: Vec<PlayerSave>,
+}
+
+/// The world-generation parameters of the saved world.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TerrainSave {
+    pub seed: u32,
+    pub octaves: u32,
+    pub frequency: f64,
+    pub amplitude: f32,
+    pub height_quantization: f32,
+    /// Worlds saved before terrain modes were introduced are continuous.
+    #[serde(default)]
+    pub mode: u8,
+    #[serde(default)]
+    pub island_radius: f32,
+    #[serde(default)]
+    pub sea_level: f32,
+    /// Worlds saved before cut heuristics were introduced are cut uniformly.
+    #[serde(default)]
+    pub cut_heuristic: u8,
+}
+
+impl TerrainSave {
+    pub fn get() -> Option<Self> {
+        let resources = entity::synchronized_resources();
+        Some(Self {
+            seed: entity::get_component(resources, terrain_seed())?,
+            octaves: entity::get_component(resources, terrain_octaves())?,
+            frequency: entity::get_component(resources, terrain_frequency())?,
+            amplitude: entity::get_component(resources, terrain_amplitude())?,
+            height_quantization: entity::get_component(resources, terrain_height_quantization())?,
+            mode: entity::get_component(resources, terrain_mode())?,
+            island_radius: entity::get_component(resources, terrain_island_radius())?,
+            sea_level: entity::get_component(resources, terrain_sea_level())?,
+            cut_heuristic: entity::get_component(resources, terrain_cut_heuristic())?,
+        })
+    }
+
+    pub fn restore(&self) {
+        entity::add_components(
+            entity::synchronized_resources(),
+            Entity::new()
+                .with(terrain_seed(), self.seed)
+                .with(terrain_octaves(), self.octaves)
+                .with(terrain_frequency(), self.frequency)
+                .with(terrain_amplitude(), self.amplitude)
+                .with(terrain_height_quantization(), self.height_quantization)
+                .with(terrain_mode(), self.mode)
+                .with(terrain_island_radius(), self.island_radius)
+                .with(terrain_sea_level(), self.sea_level)
+                .with(terrain_cut_heuristic(), self.cut_heuristic),
+        );
+    }
+}
+
+/// The saved tiles of a single chunk. Tiles with no saved state are omitted.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ChunkSave {
+    pub position: [i32; 2],
+    pub tiles: Vec<TileSave>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TileSave {
+    pub index: u8,
+    #[serde(default)]
+    pub small_crop: Option<CropSave>,
+    #[serde(default)]
+    pub medium_crop: Option<CropSave>,
+    /// Large crops are only saved on the first tile that they cover.
+    #[serde(default)]
+    pub large_crop: Option<CropSave>,
+    /// Only saved for tiles that have been tilled or planted. The soil of
+    /// other tiles is reset when their chunk is reloaded.
+    #[serde(default)]
+    pub soil: Option<SoilSave>,
+    #[serde(default)]
+    pub chest: Option<ChestSave>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SoilSave {
+    #[serde(default)]
+    pub tilled: bool,
+    pub moisture: f32,
+    pub fertility: f32,
+}
+
+impl SoilSave {
+    pub fn get(tile: EntityId) -> Option<Self> {
+        Some(Self {
+            tilled: entity::has_component(tile, is_tilled()),
+            moisture: entity::get_component(tile, soil_moisture())?,
+            fertility: entity::get_component(tile, soil_fertility())?,
+        })
+    }
+
+    pub fn restore(&self, tile: EntityId) {
+        if self.tilled {
+            entity::add_component(tile, is_tilled(), ());
+        }
+
+        entity::add_component(tile, soil_moisture(), self.moisture);
+        entity::add_component(tile, soil_fertility(), self.fertility);
+    }
+}
+
+/// The layers of crops that a tile can have.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CropLayer {
+    Small,
+    Medium,
+    Large,
+}
+
+impl CropLayer {
+    pub fn tag(&self) -> Component<()> {
+        match self {
+            CropLayer::Small => is_small_crop(),
+            CropLayer::Medium => is_medium_crop(),
+            CropLayer::Large => is_large_crop(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CropSave {
+    /// The class ID of this crop's class.
+    pub class: String,
+    /// Only used by saves without birth ticks.
+    pub age: u16,
+    #[serde(default)]
+    pub birth_tick: Option<u32>,
+    #[serde(default)]
+    pub last_tick: Option<u32>,
+    pub coords: [i32; 2],
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ChestSave {
+    /// The class ID of this chest's class.
+    pub class: String,
+    pub coords: [i32; 2],
+    /// The contents of each slot. Empty slots are None.
+    #[serde(default)]
+    pub contents: Vec<Option<ItemStackSave>>,
+}
+
+/// The saved state of a player, keyed by user ID.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PlayerSave {
+    pub user_id: String,
+    #[serde(default)]
+    pub left_held: Option<ItemStackSave>,
+    #[serde(default)]
+    pub right_held: Option<ItemStackSave>,
+    /// The contents of each inventory slot. Empty slots are None.
+    #[serde(default)]
+    pub inventory: Vec<Option<ItemStackSave>>,
+}
+
+/// A stack of items held in a hand or stored in a container slot.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ItemStackSave {
+    pub class: String,
+    pub count: u32,
+    /// The remaining uses of a tool that wears out.
+    #[serde(default)]
+    pub durability: Option<u32>,
+}
+
+/// Only the version of a save. Parsed first so that saves from newer versions
+/// of the format are never partially loaded.
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+impl WorldSave {
+    /// Loads a save from disk.
+    ///
+    /// Returns `Ok(None)` if no save exists yet.
+    pub fn load(path: &str) -> Result<Option<Self>, String> {
+        let data = match std::fs::read(path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(format!("failed to read {}: {}", path, err)),
+        };
+
+        let header: SaveHeader = serde_json::from_slice(&data)
+            .map_err(|err| format!("failed to parse save header: {}", err))?;
+
+        if header.version != SAVE_VERSION {
+            return Err(format!(
+                "unsupported save version {} (expected {})",
+                header.version, SAVE_VERSION
+            ));
+        }
+
+        serde_json::from_slice(&data)
+            .map(Some)
+            .map_err(|err| format!("failed to parse save: {}", err))
+    }
+
+    /// Writes this save to disk, replacing the old save only once the new one
+    /// has been completely written.
+    pub fn write(&self, path: &str) -> Result<(), String> {
+        let data = serde_json::to_vec_pretty(self)
+            .map_err(|err| format!("failed to serialize save: {}", err))?;
+
+        let temp_path = format!("{}.tmp", path);
+        std::fs::write(&temp_path, data)
+            .map_err(|err| format!("failed to write {}: {}", temp_path, err))?;
+        std::fs::rename(&temp_path, path)
+            .map_err(|err| format!("failed to replace {}: {}", path, err))?;
+
+        Ok(())
+    }
+}
+
+/// The in-memory state of the world save.
+///
+/// Entries are restored into the ECS as their chunks and players appear and
+/// are replaced with fresh snapshots on every autosave.
+#[derive(Default)]
+pub struct SaveStore {
+    /// Whether the save on disk may be overwritten. Cleared if the existing
+    /// save failed to load so that it isn't clobbered by an empty world.
+    pub writable: bool,
+    pub chunks: HashMap<IVec2, ChunkSave>,
+    pub players: HashMap<String, PlayerSave>,
+    /// Maps class IDs to their class entities.
+    pub classes: HashMap<String, EntityId>,
+    /// Containers whose contents have been restored, and so can be snapshotted
+    /// without losing their saved contents.
+    pub restored_containers: HashSet<EntityId>,
+    /// Restored crops whose classes haven't been spawned yet.
+    pub pending_crops: HashMap<String, Vec<(CropSave, CropLayer)>>,
+    /// Restored chests whose classes haven't been spawned yet.
+    pub pending_chests: HashMap<String, Vec<ChestSave>>,
+}
+
+impl SaveStore {
+    pub fn from_save(save: WorldSave) -> Self {
+        Self {
+            writable: true,
+            chunks: save
+                .chunks
+                .into_iter()
+                .map(|chunk| (IVec2::from_array(chunk.position), chunk))
+                .collect(),
+            players: save
+                .players
+                .into_iter()
+                .map(|player| (player.user_id.clone(), player))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn on_class(&mut self, class: EntityId, id: String) {
+        if let Some(pending) = self.pending_crops.remove(&id) {
+            for (crop, layer) in pending {
+                spawn_crop(class, &crop, layer);
+            }
+        }
+
+        self.classes.insert(id.clone(), class);
+
+        if let Some(pending) = self.pending_chests.remove(&id) {
+            for chest in pending {
+                self.spawn_chest(class, &chest);
+            }
+        }
+    }
+
+    pub fn restore_chunk(&mut self, position: IVec2, tiles: &[EntityId]) {
+        let Some(saved) = self.chunks.get(&position) else {
+            return;
+        };
+
+        let mut crops = Vec::new();
+        let mut chests = Vec::new();
+        for tile in saved.tiles.iter() {
+            if let (Some(soil), Some(e)) = (&tile.soil, tiles.get(tile.index as usize)) {
+                soil.restore(*e);
+            }
+
+            let layers = [
+                (&tile.small_crop, CropLayer::Small),
+                (&tile.medium_crop, CropLayer::Medium),
+                (&tile.large_crop, CropLayer::Large),
+            ];
+
+            for (crop, layer) in layers {
+                if let Some(crop) = crop {
+                    crops.push((crop.clone(), layer));
+                }
+            }
+
+            if let Some(chest) = &tile.chest {
+                chests.push(chest.clone());
+            }
+        }
+
+        for (crop, layer) in crops {
+            match self.classes.get(&crop.class) {
+                Some(class) => spawn_crop(*class, &crop, layer),
+                None => self
+                    .pending_crops
+                    .entry(crop.class.clone())
+                    .or_default()
+                    .push((crop, layer)),
+            }
+        }
+
+        for chest in chests {
+            match self.classes.get(&chest.class) {
+                Some(class) => self.spawn_chest(*class, &chest),
+                None => self
+                    .pending_chests
+                    .entry(chest.class.clone())
+                    .or_default()
+                    .push(chest),
+            }
+        }
+    }
+
+    /// Spawns a restored chest along with its contents.
+    ///
+    /// Items whose classes no longer exist are dropped from the chest.
+    fn spawn_chest(&self, class: EntityId, chest: &ChestSave) {
+        let mut classes = Vec::with_capacity(chest.contents.len());
+        let mut counts = Vec::with_capacity(chest.contents.len());
+        let mut durabilities = Vec::with_capacity(chest.contents.len());
+
+        for stack in chest.contents.iter() {
+            let item_class = stack.as_ref().and_then(|stack| {
+                let item_class = self.classes.get(&stack.class).copied();
+                if item_class.is_none() {
+                    eprintln!("chest item class {} no longer exists", stack.class);
+                }
+
+                item_class.map(|item_class| (item_class, stack))
+            });
+
+            match item_class {
+                Some((item_class, stack)) => {
+                    classes.push(item_class);
+                    counts.push(stack.count);
+                    durabilities.push(stack.durability.unwrap_or(0));
+                }
+                None => {
+                    classes.push(EntityId::null());
+                    counts.push(0);
+                    durabilities.push(0);
+                }
+            }
+        }
+
+        Entity::new()
+            .with(class_ref(), class)
+            .with(chest_coords(), IVec2::from_array(chest.coords))
+            .with(container_classes(), classes)
+            .with(container_counts(), counts)
+            .with(container_durabilities(), durabilities)
+            .spawn();
+    }
+
+    pub fn snapshot_chunk(&mut self, position: IVec2, tiles: &[EntityId]) {
+        let mut saved_tiles = Vec::new();
+        for (index, tile) in tiles.iter().enumerate() {
+            let small_crop = entity::get_component(*tile, small_crop_occupant())
+                .filter(|occupant| !occupant.is_null())
+                .and_then(save_crop);
+
+            let medium_crop = entity::get_component(*tile, medium_crop_occupant())
+                .filter(|occupant| !occupant.is_null())
+                .and_then(save_crop);
+
+            let large_crop = entity::get_component(*tile, large_crop_occupant())
+                .filter(|occupant| entity::get_component(*occupant, on_tile()) == Some(*tile))
+                .and_then(save_crop);
+
+            let chest = entity::get_component(*tile, chest_occupant())
+                .filter(|occupant| !occupant.is_null())
+                .and_then(save_chest);
+
+            let is_planted = small_crop.is_some() || medium_crop.is_some() || large_crop.is_some();
+            if is_planted || chest.is_some() || entity::has_component(*tile, is_tilled()) {
+                saved_tiles.push(TileSave {
+                    index: index as u8,
+                    small_crop,
+                    medium_crop,
+                    large_crop,
+                    soil: SoilSave::get(*tile),
+                    chest,
+                });
+            }
+        }
+
+        // crops that haven't been restored yet must not be lost
+        for (crop, layer) in self.pending_crops.values().flatten() {
+            let coords = IVec2::from_array(crop.coords);
+            let chunk_size = IVec2::splat(CHUNK_SIZE as i32);
+            if coords.div_euclid(chunk_size) != position {
+                continue;
+            }
+
+            let fine = coords.rem_euclid(chunk_size);
+            let index = fine.y * CHUNK_SIZE as i32 + fine.x;
+            let saved_crop = |expected| Some(crop.clone()).filter(|_| *layer == expected);
+            saved_tiles.push(TileSave {
+                index: index as u8,
+                small_crop: saved_crop(CropLayer::Small),
+                medium_crop: saved_crop(CropLayer::Medium),
+                large_crop: saved_crop(CropLayer::Large),
+                soil: None,
+                chest: None,
+            });
+        }
+
+        // neither may chests that haven't been restored yet
+        for chest in self.pending_chests.values().flatten() {
+            let coords = IVec2::from_array(chest.coords);
+            let chunk_size = IVec2::splat(CHUNK_SIZE as i32);
+            if coords.div_euclid(chunk_size) != position {
+                continue;
+            }
+
+            let fine = coords.rem_euclid(chunk_size);
+            let index = fine.y * CHUNK_SIZE as i32 + fine.x;
+            saved_tiles.push(TileSave {
+                index: index as u8,
+                small_crop: None,
+                medium_crop: None,
+                large_crop: None,
+                soil: None,
+                chest: Some(chest.clone()),
+            });
+        }
+
+        if saved_tiles.is_empty() {
+            self.chunks.remove(&position);
+        } else {
+            saved_tiles.sort_by_key(|tile| tile.index);
+            let saved = ChunkSave {
+                position: position.to_array(),
+                tiles: saved_tiles,
+            };
+
+            self.chunks.insert(position, saved);
+        }
+    }
+
+    pub fn restore_player(&self, uid: &str, left_hand: EntityId, right_hand: EntityId) {
+        let Some(saved) = self.players.get(uid) else {
+            return;
+        };
+
+        let restore_hand = |hand: EntityId, held: &Option<ItemStackSave>| {
+            let Some(held) = held else {
+                return;
+            };
+
+            let Some(item_class) = self.classes.get(&held.class) else {
+                eprintln!("held item class {} of {} no longer exists", held.class, uid);
+                return;
+            };
+
+            let mut instance = Entity::new()
+                .with(class(), *item_class)
+                .with(quantity(), held.count.max(1));
+
+            if let Some(uses) = held.durability {
+                instance.set(durability(), uses);
+            }
+
+            // replace whatever the hand was given before it was restored
+            let old = entity::get_component(hand, held_ref()).unwrap_or_default();
+            entity::add_component(hand, held_ref(), instance.spawn());
+            if !old.is_null() {
+                entity::despawn_recursive(old);
+            }
+        };
+
+        restore_hand(left_hand, &saved.left_held);
+        restore_hand(right_hand, &saved.right_held);
+    }
+
+    pub fn snapshot_player(&mut self, uid: String, left_hand: EntityId, right_hand: EntityId) {
+        let save_hand = |hand: EntityId| {
+            let held = entity::get_component(hand, held_ref())?;
+            let held_class = entity::get_component(held, class())?;
+            Some(ItemStackSave {
+                class: entity::get_component(held_class, class_id())?,
+                count: entity::get_component(held, quantity()).unwrap_or(1),
+                durability: entity::get_component(held, durability()),
+            })
+        };
+
+        // inventories are snapshotted separately whenever they change
+        let inventory = self
+            .players
+            .get(&uid)
+            .map(|saved| saved.inventory.clone())
+            .unwrap_or_default();
+
+        let player = PlayerSave {
+            user_id: uid.clone(),
+            left_held: save_hand(left_hand),
+            right_held: save_hand(right_hand),
+            inventory,
+        };
+
+        self.players.insert(uid, player);
+    }
+
+    pub fn restore_inventory(&mut self, uid: &str, container: EntityId) {
+        self.restored_containers.insert(container);
+
+        let Some(saved) = self.players.get(uid) else {
+            return;
+        };
+
+        let mut classes = entity::get_component(container, container_classes()).unwrap_or_default();
+        let mut counts = entity::get_component(container, container_counts()).unwrap_or_default();
+        let mut durabilities =
+            entity::get_component(container, container_durabilities()).unwrap_or_default();
+        counts.resize(classes.len(), 0);
+        durabilities.resize(classes.len(), 0);
+
+        for (slot, stack) in saved.inventory.iter().enumerate() {
+            let Some(stack) = stack else {
+                continue;
+            };
+
+            if slot >= classes.len() {
+                eprintln!("inventory slot {} of {} no longer exists", slot, uid);
+                break;
+            }
+
+            match self.classes.get(&stack.class) {
+                Some(item_class) => {
+                    classes[slot] = *item_class;
+                    counts[slot] = stack.count;
+                    durabilities[slot] = stack.durability.unwrap_or(0);
+                }
+                None => eprintln!("item class {} of {} no longer exists", stack.class, uid),
+            }
+        }
+
+        entity::add_component(container, container_classes(), classes);
+        entity::add_component(container, container_counts(), counts);
+        entity::add_component(container, container_durabilities(), durabilities);
+    }
+
+    pub fn snapshot_inventory(&mut self, uid: String, container: EntityId) {
+        let classes = entity::get_component(container, container_classes()).unwrap_or_default();
+        let counts = entity::get_component(container, container_counts()).unwrap_or_default();
+        let durabilities =
+            entity::get_component(container, container_durabilities()).unwrap_or_default();
+
+        let inventory = classes
+            .iter()
+            .enumerate()
+            .map(|(slot, item_class)| {
+                Some(ItemStackSave {
+                    class: entity::get_component(*item_class, class_id())?,
+                    count: counts.get(slot).copied().unwrap_or(1),
+                    durability: durabilities.get(slot).copied().filter(|uses| *uses > 0),
+                })
+            })
+            .collect();
+
+        let player = self.players.entry(uid.clone()).or_insert_with(|| PlayerSave {
+            user_id: uid,
+            left_held: None,
+            right_held: None,
+            inventory: Vec::new(),
+        });
+
+        player.inventory = inventory;
+    }
+
+    pub fn to_save(&self) -> WorldSave {
+        let mut chunks: Vec<_> = self.chunks.values().cloned().collect();
+        chunks.sort_by_key(|chunk| chunk.position);
+
+        let mut players: Vec<_> = self.players.values().cloned().collect();
+        players.sort_by(|a, b| a.user_id.cmp(&b.user_id));
+
+        WorldSave {
+            version: SAVE_VERSION,
+            time_of_day: entity::get_component(entity::synchronized_resources(), time_of_day()),
+            day: entity::get_component(entity::synchronized_resources(), day()),
+            growth_tick: entity::get_component(entity::resources(), growth_tick()),
+            terrain: TerrainSave::get(),
+            chunks,
+            players,
+        }
+    }
+}
+
+fn spawn_crop(class: EntityId, crop: &CropSave, layer: CropLayer) {
+    let mut entity = Entity::new()
+        .with(layer.tag(), ())
+        .with(class_ref(), class)
+        .with(coords(), IVec2::from_array(crop.coords));
+
+    if let Some(birth) = crop.birth_tick {
+        entity.set(birth_tick(), birth);
+        entity.set(last_growth_tick(), crop.last_tick.unwrap_or(birth));
+    } else {
+        entity.set(age(), crop.age);
+    }
+
+    entity.spawn();
+}
+
+fn save_crop(crop: EntityId) -> Option<CropSave> {
+    let class = entity::get_component(crop, class_ref())?;
+    let birth = entity::get_component(crop, birth_tick());
+    let now = entity::get_component(entity::resources(), growth_tick()).unwrap_or(0);
+
+    Some(CropSave {
+        class: entity::get_component(class, class_id())?,
+        age: birth
+            .map(|birth| now.saturating_sub(birth).min(u16::MAX as u32) as u16)
+            .or_else(|| entity::get_component(crop, age()))
+            .unwrap_or(0),
+        birth_tick: birth,
+        last_tick: entity::get_component(crop, last_growth_tick()),
+        coords: entity::get_component(crop, coords())?.to_array(),
+    })
+}
+
+fn save_chest(chest: EntityId) -> Option<ChestSave> {
+    let class = entity::get_component(chest, class_ref())?;
+    let classes = entity::get_component(chest, container_classes()).unwrap_or_default();
+    let counts = entity::get_component(chest, container_counts()).unwrap_or_default();
+    let durabilities = entity::get_component(chest, container_durabilities()).unwrap_or_default();
+
+    let contents = classes
+        .iter()
+        .enumerate()
+        .map(|(slot, item_class)| {
+            Some(ItemStackSave {
+                class: entity::get_component(*item_class, class_id())?,
+                count: counts.get(slot).copied().unwrap_or(1),
+                durability: durabilities.get(slot).copied().filter(|uses| *uses > 0),
+            })
+        })
+        .collect();
+
+    Some(ChestSave {
+        class: entity::get_component(class, class_id())?,
+        coords: entity::get_component(chest, chest_coords())?.to_array(),
+        contents,
+    })
+}
+
+#[main]
+fn main() {
+    let (store, new_world) = match WorldSave::load(SAVE_PATH) {
+        Ok(Some(save)) => {
+            eprintln!("loaded world save from {}", SAVE_PATH);
+
+            if let Some(time) = save.time_of_day {
+                entity::add_component(entity::synchronized_resources(), time_of_day(), time);
+            }
+
+            if let Some(today) = save.day {
+                entity::add_component(entity::synchronized_resources(), day(), today);
+            }
+
+            // crop birth ticks are relative to the saved growth tick
+            if let Some(tick) = save.growth_tick {
+                entity::add_component(entity::resources(), growth_tick(), tick);
+            }
+
+            // the terrain must be generated from the same parameters as before
+            if let Some(terrain) = save.terrain.as_ref() {
+                terrain.restore();
+            }
+
+            (SaveStore::from_save(save), false)
+        }
+        Ok(None) => {
+            eprintln!("no world save found; starting a new world");
+            let store = SaveStore {
+                writable: true,
+                ..Default::default()
+            };
+
+            (store, true)
+        }
+        Err(err) => {
+            eprintln!("{}; autosaving is disabled", err);
+            (SaveStore::default(), true)
+        }
+    };
+
+    entity::add_component(entity::resources(), is_new_world(), new_world);
+
+    let store = Arc::new(Mutex::new(store));
+
+    if !entity::has_component(entity::resources(), autosave_interval()) {
+        entity::add_component(
+            entity::resources(),
+            autosave_interval(),
+            DEFAULT_AUTOSAVE_INTERVAL,
+        );
+    }
+
+    store.on_event(
+        spawn_query(class_id()).requires(is_class()),
+        move |store, e, id| store.on_class(e, id),
+    );
+
+    store.on_event(
+        spawn_query((chunk(), chunk_tile_refs())),
+        move |store, _e, (position, tiles)| store.restore_chunk(position, &tiles),
+    );
+
+    store.on_local_message(move |store, _, data: OnChunkUnload| {
+        let tiles = entity::get_component(data.chunk, chunk_tile_refs()).unwrap_or_default();
+        store.snapshot_chunk(data.position, &tiles);
+    });
+
+    store.on_event(
+        spawn_query((user_id(), left_hand_ref(), right_hand_ref())).requires(is_player()),
+        move |store, e, (uid, left, right)| {
+            if store.players.contains_key(&uid) {
+                store.restore_player(&uid, left, right);
+            } else {
+                entity::add_component(e, is_new_player(), ());
+            }
+        },
+    );
+
+    store.on_event(
+        despawn_query((user_id(), left_hand_ref(), right_hand_ref())).requires(is_player()),
+        move |store, _e, (uid, left, right)| store.snapshot_player(uid, left, right),
+    );
+
+    store.on_event(
+        spawn_query((owner_ref(), container_classes())),
+        move |store, e, (owner, _classes)| {
+            if let Some(uid) = entity::get_component(owner, user_id()) {
+                store.restore_inventory(&uid, e);
+            }
+        },
+    );
+
+    store.on_event(despawn_query(container_classes()), move |store, e, _classes| {
+        store.restored_containers.remove(&e);
+    });
+
+    store.on_change(
+        change_query((owner_ref(), container_classes(), container_counts()))
+            .track_change((container_classes(), container_counts(), container_durabilities())),
+        move |store, e, (owner, _classes, _counts)| {
+            if !store.restored_containers.contains(&e) {
+                return;
+            }
+
+            if let Some(uid) = entity::get_component(owner, user_id()) {
+                store.snapshot_inventory(uid, e);
+            }
+        },
+    );
+
+    let chunks = query((chunk(), chunk_tile_refs())).build();
+    let players = query((user_id(), left_hand_ref(), right_hand_ref()))
+        .requires(is_player())
+        .build();
+
+    run_async(async move {
+        loop {
+            let interval = entity::get_component(entity::resources(), autosave_interval())
+                .unwrap_or(DEFAULT_AUTOSAVE_INTERVAL);
+
+            sleep(interval).await;
+
+            let mut store = store.lock().unwrap();
+            if !store.writable {
+                continue;
+            }
+
+            for (_e, (position, tiles)) in chunks.evaluate() {
+                store.snapshot_chunk(position, &tiles);
+            }
+
+            for (_e, (uid, left, right)) in players.evaluate() {
+                store.snapshot_player(uid, left, right);
+            }
+
+            match store.to_save().write(SAVE_PATH) {
+                Ok(()) => eprintln!("autosaved world to {}", SAVE_PATH),
+                Err(err) => eprintln!("{}", err),
+            }
+        }
+    });
+}
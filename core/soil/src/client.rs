@@ -0,0 +1,189 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use ambient_api::{core::rendering::components::color, prelude::*};
+
+use flowerpot_common::{ActorExt, SystemExt};
+use packages::{
+    map::components::{chunk, chunk_tile_index, chunk_tile_refs, in_chunk},
+    terrain::components::{highlight_tile, mesh_positions},
+    this::{components::*, messages::*},
+};
+
+mod shared;
+
+/// The color of completely dry tilled soil.
+pub const DRY_SOIL_COLOR: Vec4 = Vec4::new(0.55, 0.4, 0.26, 1.0);
+
+/// The color of completely wet tilled soil.
+pub const WET_SOIL_COLOR: Vec4 = Vec4::new(0.24, 0.16, 0.1, 1.0);
+
+/// Converts a networked soil value back into the range [0, 1].
+pub fn dequantize(value: u8) -> f32 {
+    value as f32 / 255.0
+}
+
+/// Adds, updates, or removes the overlay drawing a tile's tilled soil.
+fn update_overlay(chunk_entity: EntityId, tile: EntityId, tile_idx: u8) {
+    let overlay = entity::get_component(tile, soil_overlay_ref());
+
+    if !entity::has_component(tile, is_tilled()) {
+        if let Some(overlay) = overlay {
+            entity::despawn_recursive(overlay);
+            entity::remove_component(tile, soil_overlay_ref());
+        }
+
+        return;
+    }
+
+    let moisture = entity::get_component(tile, soil_moisture()).unwrap_or(0.0);
+    let soil_color = DRY_SOIL_COLOR.lerp(WET_SOIL_COLOR, moisture);
+
+    if let Some(overlay) = overlay {
+        entity::add_component(overlay, color(), soil_color);
+        return;
+    }
+
+    // tile highlights are built from the chunk's mesh, so wait for it
+    if !entity::has_component(chunk_entity, mesh_positions()) {
+        return;
+    }
+
+    let overlay = Entity::new()
+        .with(in_chunk(), chunk_entity)
+        .with(chunk_tile_index(), tile_idx)
+        .with(highlight_tile(), ())
+        .with(color(), soil_color)
+        .spawn();
+
+    entity::add_component(tile, soil_overlay_ref(), overlay);
+}
+
+fn apply_tile_soil(
+    chunk_entity: EntityId,
+    tile: EntityId,
+    tile_idx: u8,
+    tilled: bool,
+    moisture: u8,
+    fertility: u8,
+) {
+    if tilled {
+        entity::add_component(tile, is_tilled(), ());
+    } else if entity::has_component(tile, is_tilled()) {
+        entity::remove_component(tile, is_tilled());
+    }
+
+    entity::add_component(tile, soil_moisture(), dequantize(moisture));
+    entity::add_component(tile, soil_fertility(), dequantize(fertility));
+
+    update_overlay(chunk_entity, tile, tile_idx);
+}
+
+fn apply_soil(chunk_entity: EntityId, tiles: &[EntityId], data: &UpdateChunkSoil) {
+    for (idx, tile) in tiles.iter().copied().enumerate() {
+        let (Some(tilled), Some(moisture), Some(fertility)) = (
+            data.tilled.get(idx),
+            data.moisture.get(idx),
+            data.fertility.get(idx),
+        ) else {
+            eprintln!("soil update for chunk {} is missing tiles", data.chunk);
+            return;
+        };
+
+        apply_tile_soil(chunk_entity, tile, idx as u8, *tilled, *moisture, *fertility);
+    }
+}
+
+/// Applies a partial update either to a loaded chunk's tiles or to the full
+/// update that's waiting for the chunk to load.
+fn apply_tile_update(
+    chunk_entity: Option<EntityId>,
+    pending: Option<&mut UpdateChunkSoil>,
+    data: &UpdateTileSoil,
+) {
+    let tiles = chunk_entity.and_then(|e| entity::get_component(e, chunk_tile_refs()));
+    let updates = data
+        .indices
+        .iter()
+        .zip(data.tilled.iter())
+        .zip(data.moisture.iter().zip(data.fertility.iter()));
+
+    match (chunk_entity, tiles, pending) {
+        (Some(chunk_entity), Some(tiles), _) => {
+            for ((idx, tilled), (moisture, fertility)) in updates {
+                if let Some(tile) = tiles.get(*idx as usize) {
+                    apply_tile_soil(chunk_entity, *tile, *idx, *tilled, *moisture, *fertility);
+                }
+            }
+        }
+        (_, _, Some(pending)) => {
+            for ((idx, tilled), (moisture, fertility)) in updates {
+                let idx = *idx as usize;
+                if idx < pending.tilled.len() {
+                    pending.tilled[idx] = *tilled;
+                    pending.moisture[idx] = *moisture;
+                    pending.fertility[idx] = *fertility;
+                }
+            }
+        }
+        // the chunk's full update will arrive when it starts being observed
+        _ => {}
+    }
+}
+
+#[main]
+fn main() {
+    let chunks = flowerpot_common::init_map(chunk());
+
+    // updates for chunks that haven't been loaded on this client yet
+    let pending: Arc<Mutex<HashMap<IVec2, UpdateChunkSoil>>> = Default::default();
+
+    pending.on_message({
+        let chunks = chunks.clone();
+        move |pending, _, data: UpdateTileSoil| {
+            let chunk_entity = chunks.lock().unwrap().get(&data.chunk).copied();
+            apply_tile_update(chunk_entity, pending.get_mut(&data.chunk), &data);
+        }
+    });
+
+    pending.on_message(move |pending, _, data: UpdateChunkSoil| {
+        let chunk_entity = chunks.lock().unwrap().get(&data.chunk).copied();
+        let tiles = chunk_entity.and_then(|e| entity::get_component(e, chunk_tile_refs()));
+        match (chunk_entity, tiles) {
+            (Some(chunk_entity), Some(tiles)) => apply_soil(chunk_entity, &tiles, &data),
+            _ => {
+                pending.insert(data.chunk, data);
+            }
+        }
+    });
+
+    pending.on_event(
+        spawn_query((chunk(), chunk_tile_refs())),
+        move |pending, e, (position, tiles)| {
+            if let Some(data) = pending.remove(&position) {
+                apply_soil(e, &tiles, &data);
+            }
+        },
+    );
+
+    // draw the tilled tiles whose overlays were waiting on the chunk's mesh
+    spawn_query((chunk_tile_refs(), mesh_positions())).bind(move |entities| {
+        for (e, (tiles, _positions)) in entities {
+            for (idx, tile) in tiles.into_iter().enumerate() {
+                update_overlay(e, tile, idx as u8);
+            }
+        }
+    });
+
+    despawn_query(chunk_tile_refs()).bind(move |entities| {
+        for (_e, tiles) in entities {
+            for tile in tiles {
+                if let Some(overlay) = entity::get_component(tile, soil_overlay_ref()) {
+                    entity::despawn_recursive(overlay);
+                }
+            }
+        }
+    });
+}
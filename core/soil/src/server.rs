@@ -0,0 +1,322 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use ambient_api::{core::player::components::user_id, prelude::*};
+
+use flowerpot_common::{ActorExt, SystemExt};
+use packages::{
+    map::components::{chunk, chunk_tile_refs, in_chunk},
+    region_networking::{components::players_observing, messages::LoadPlayerRegion},
+    this::{components::*, messages::*},
+    weather::{components::weather, messages::OnNewDay},
+};
+
+mod shared;
+
+/// The moisture of soil that has never been watered.
+pub const DEFAULT_SOIL_MOISTURE: f32 = 0.5;
+
+/// The fertility of soil that has never been farmed. Soil recovers up to this.
+pub const DEFAULT_SOIL_FERTILITY: f32 = 0.75;
+
+/// The time in seconds between each evaporation and recovery update.
+pub const SOIL_TICK_INTERVAL: f32 = 10.0;
+
+/// How much moisture untilled soil loses every soil tick when it isn't raining.
+pub const EVAPORATION_RATE: f32 = 0.01;
+
+/// Multiplies the evaporation rate of tilled soil.
+pub const TILLED_EVAPORATION_MULTIPLIER: f32 = 0.5;
+
+/// How much moisture soil gains every soil tick while it's raining.
+pub const RAIN_MOISTURE: f32 = 0.05;
+
+/// The value of the weather component while it's raining.
+const RAINING: u8 = 1;
+
+/// How much fertility depleted soil recovers every soil tick.
+pub const FERTILITY_RECOVERY_RATE: f32 = 0.002;
+
+/// The time in seconds between each sync of changed chunks to their observers.
+pub const SOIL_SYNC_INTERVAL: f32 = 0.5;
+
+/// Quantizes a soil value in the range [0, 1] for networking.
+pub fn quantize(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Adds a change onto the exact value of a soil component, which is kept
+/// separately so that changes too small to survive quantization still add up.
+///
+/// Returns the new value only if it differs once quantized.
+pub fn drift(
+    exact: &mut HashMap<EntityId, f32>,
+    tile: EntityId,
+    current: f32,
+    change: f32,
+    limit: f32,
+) -> Option<f32> {
+    // the component may have been changed by something else, like watering
+    let value = exact
+        .remove(&tile)
+        .filter(|value| quantize(*value) == quantize(current))
+        .unwrap_or(current);
+
+    let value = (value + change).clamp(0.0, limit);
+    exact.insert(tile, value);
+    Some(value).filter(|value| quantize(*value) != quantize(current))
+}
+
+/// The networked soil state of a chunk's tiles, ordered by tile index.
+#[derive(Clone, PartialEq)]
+pub struct ChunkSoil {
+    pub tilled: Vec<bool>,
+    pub moisture: Vec<u8>,
+    pub fertility: Vec<u8>,
+}
+
+impl ChunkSoil {
+    pub fn get(tiles: &[EntityId]) -> Self {
+        let mut soil = Self {
+            tilled: Vec::with_capacity(tiles.len()),
+            moisture: Vec::with_capacity(tiles.len()),
+            fertility: Vec::with_capacity(tiles.len()),
+        };
+
+        for tile in tiles.iter().copied() {
+            let moisture = entity::get_component(tile, soil_moisture());
+            let fertility = entity::get_component(tile, soil_fertility());
+            soil.tilled.push(entity::has_component(tile, is_tilled()));
+            soil.moisture.push(quantize(moisture.unwrap_or(DEFAULT_SOIL_MOISTURE)));
+            soil.fertility.push(quantize(fertility.unwrap_or(DEFAULT_SOIL_FERTILITY)));
+        }
+
+        soil
+    }
+
+    pub fn to_message(&self, position: IVec2) -> UpdateChunkSoil {
+        UpdateChunkSoil::new(
+            position,
+            self.tilled.clone(),
+            self.moisture.clone(),
+            self.fertility.clone(),
+        )
+    }
+
+    /// Creates a message with only the tiles that differ from an older state.
+    ///
+    /// Returns None if no tiles have changed.
+    pub fn diff_message(&self, position: IVec2, old: &Self) -> Option<UpdateTileSoil> {
+        let mut update = UpdateTileSoil::new(position, vec![], vec![], vec![], vec![]);
+        for idx in 0..self.tilled.len() {
+            let tile = (self.tilled[idx], self.moisture[idx], self.fertility[idx]);
+            let old_tile = (
+                old.tilled.get(idx).copied(),
+                old.moisture.get(idx).copied(),
+                old.fertility.get(idx).copied(),
+            );
+
+            if old_tile == (Some(tile.0), Some(tile.1), Some(tile.2)) {
+                continue;
+            }
+
+            update.indices.push(idx as u8);
+            update.tilled.push(tile.0);
+            update.moisture.push(tile.1);
+            update.fertility.push(tile.2);
+        }
+
+        Some(update).filter(|update| !update.indices.is_empty())
+    }
+}
+
+/// Tracks which chunks' soil has changed since it was last sent to clients.
+#[derive(Default)]
+pub struct SoilSync {
+    pub dirty: HashSet<EntityId>,
+    pub sent: HashMap<EntityId, ChunkSoil>,
+}
+
+impl SoilSync {
+    /// Sends the soil of every changed chunk to the players observing it.
+    pub fn flush(&mut self) {
+        for chunk_entity in std::mem::take(&mut self.dirty) {
+            let Some(position) = entity::get_component(chunk_entity, chunk()) else {
+                self.sent.remove(&chunk_entity);
+                continue;
+            };
+
+            let Some(tiles) = entity::get_component(chunk_entity, chunk_tile_refs()) else {
+                continue;
+            };
+
+            // only send the tiles that have changed since the last update
+            let soil = ChunkSoil::get(&tiles);
+            let diff = match self.sent.get(&chunk_entity) {
+                Some(old) => match soil.diff_message(position, old) {
+                    Some(diff) => Some(diff),
+                    None => continue,
+                },
+                None => None,
+            };
+
+            let observers =
+                entity::get_component(chunk_entity, players_observing()).unwrap_or_default();
+            for player in observers {
+                let Some(uid) = entity::get_component(player, user_id()) else {
+                    continue;
+                };
+
+                match diff.as_ref() {
+                    Some(diff) => diff.send_client_targeted_reliable(uid),
+                    None => soil.to_message(position).send_client_targeted_reliable(uid),
+                }
+            }
+
+            self.sent.insert(chunk_entity, soil);
+        }
+    }
+}
+
+#[main]
+fn main() {
+    let sync = Arc::new(Mutex::new(SoilSync::default()));
+
+    // give every new tile some soil to start with
+    sync.on_event(spawn_query(chunk_tile_refs()), move |sync, e, tiles| {
+        for tile in tiles {
+            // the soil may have already been restored from a save
+            if !entity::has_component(tile, soil_moisture()) {
+                entity::add_component(tile, soil_moisture(), DEFAULT_SOIL_MOISTURE);
+            }
+
+            if !entity::has_component(tile, soil_fertility()) {
+                entity::add_component(tile, soil_fertility(), DEFAULT_SOIL_FERTILITY);
+            }
+        }
+
+        sync.dirty.insert(e);
+    });
+
+    sync.on_event(despawn_query(chunk_tile_refs()), move |sync, e, _tiles| {
+        sync.dirty.remove(&e);
+        sync.sent.remove(&e);
+    });
+
+    sync.on_change(
+        change_query((in_chunk(), soil_moisture(), soil_fertility()))
+            .track_change((soil_moisture(), soil_fertility())),
+        move |sync, _e, (chunk_entity, _moisture, _fertility)| {
+            sync.dirty.insert(chunk_entity);
+        },
+    );
+
+    sync.on_event(
+        spawn_query(in_chunk()).requires(is_tilled()),
+        move |sync, _e, chunk_entity| {
+            sync.dirty.insert(chunk_entity);
+        },
+    );
+
+    sync.on_event(
+        despawn_query(in_chunk()).requires(is_tilled()),
+        move |sync, _e, chunk_entity| {
+            sync.dirty.insert(chunk_entity);
+        },
+    );
+
+    // send the whole chunk to players that start observing it
+    sync.on_local_message(move |_sync, _, data: LoadPlayerRegion| {
+        let Some(position) = entity::get_component(data.region, chunk()) else {
+            return;
+        };
+
+        let Some(tiles) = entity::get_component(data.region, chunk_tile_refs()) else {
+            return;
+        };
+
+        ChunkSoil::get(&tiles)
+            .to_message(position)
+            .send_client_targeted_reliable(data.player_uid);
+    });
+
+    run_async({
+        let sync = sync.clone();
+        async move {
+            loop {
+                sleep(SOIL_SYNC_INTERVAL).await;
+                sync.lock().unwrap().flush();
+            }
+        }
+    });
+
+    let moist_tiles = query((in_chunk(), soil_moisture())).build();
+    let fertile_tiles = query(soil_fertility()).build();
+    let watered_tiles = query(()).requires(is_watered_today()).build();
+
+    // tiles can be watered again every day
+    OnNewDay::subscribe(move |_, _data| {
+        for (tile, _) in watered_tiles.evaluate() {
+            entity::remove_component(tile, is_watered_today());
+        }
+    });
+
+    run_async(async move {
+        // the unquantized soil values of each tile
+        let mut exact_moisture = HashMap::new();
+        let mut exact_fertility = HashMap::new();
+
+        loop {
+            sleep(SOIL_TICK_INTERVAL).await;
+
+            // only keep the values of tiles that still exist
+            let mut old_moisture = std::mem::take(&mut exact_moisture);
+            let mut old_fertility = std::mem::take(&mut exact_fertility);
+
+            for (tile, (chunk_entity, moisture)) in moist_tiles.evaluate() {
+                let is_raining = entity::get_component(chunk_entity, weather()) == Some(RAINING);
+                let change = if is_raining {
+                    RAIN_MOISTURE
+                } else if entity::has_component(tile, is_tilled()) {
+                    -EVAPORATION_RATE * TILLED_EVAPORATION_MULTIPLIER
+                } else {
+                    -EVAPORATION_RATE
+                };
+
+                // writing every tile would reschedule every crop's growth
+                let new_moisture = drift(&mut old_moisture, tile, moisture, change, 1.0);
+                if let Some(new_moisture) = new_moisture {
+                    entity::set_component(tile, soil_moisture(), new_moisture);
+                }
+
+                if let Some(exact) = old_moisture.remove(&tile) {
+                    exact_moisture.insert(tile, exact);
+                }
+            }
+
+            for (tile, fertility) in fertile_tiles.evaluate() {
+                if fertility >= DEFAULT_SOIL_FERTILITY {
+                    continue;
+                }
+
+                let recovered = drift(
+                    &mut old_fertility,
+                    tile,
+                    fertility,
+                    FERTILITY_RECOVERY_RATE,
+                    DEFAULT_SOIL_FERTILITY,
+                );
+
+                if let Some(recovered) = recovered {
+                    entity::set_component(tile, soil_fertility(), recovered);
+                }
+
+                if let Some(exact) = old_fertility.remove(&tile) {
+                    exact_fertility.insert(tile, exact);
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,68 @@
+use ambient_api::{
+    core::{
+        primitives::components::cube,
+        rendering::components::color,
+        transform::{
+            components::{local_to_world, scale, translation},
+            concepts::make_transformable,
+        },
+    },
+    prelude::*,
+};
+
+use flowerpot_common::{impl_remote_update, RemoteEntityStore};
+use packages::{
+    map::components::position,
+    region_networking::components::remote_entity,
+    terrain::components::altitude,
+    things::components::model_prefab_url,
+    this::{components::*, messages::*},
+};
+
+mod shared;
+
+impl_remote_update!(UpdateChestCoords);
+
+/// The size of the box drawn for chests without a model.
+const CHEST_BOX_SIZE: Vec3 = Vec3::new(0.8, 0.6, 0.6);
+
+/// The color of the box drawn for chests without a model.
+const CHEST_BOX_COLOR: Vec4 = Vec4::new(0.45, 0.3, 0.15, 1.0);
+
+#[main]
+fn main() {
+    shared::init_shared();
+
+    let remote_store = RemoteEntityStore::new(remote_entity());
+
+    remote_store.subscribe_update::<UpdateChestCoords>(move |e, data| {
+        entity::add_component(e, chest_coords(), data.coords);
+    });
+
+    // is_chest comes from the chest's class, so by now the model has too
+    spawn_query((position(), altitude()))
+        .requires((chest_coords(), is_chest()))
+        .bind(move |entities| {
+            for (e, (position, altitude)) in entities {
+                if entity::has_component(e, model_prefab_url()) {
+                    entity::add_components(
+                        e,
+                        make_transformable()
+                            .with(translation(), position.extend(altitude))
+                            .with(local_to_world(), Mat4::IDENTITY),
+                    );
+                } else {
+                    let center = position.extend(altitude + CHEST_BOX_SIZE.z / 2.0);
+                    entity::add_components(
+                        e,
+                        make_transformable()
+                            .with(translation(), center)
+                            .with(scale(), CHEST_BOX_SIZE)
+                            .with(local_to_world(), Mat4::IDENTITY)
+                            .with(cube(), ())
+                            .with(color(), CHEST_BOX_COLOR),
+                    );
+                }
+            }
+        });
+}
@@ -0,0 +1,53 @@
+use ambient_api::prelude::*;
+
+use packages::{
+    items::components::{container_classes, container_counts, container_durabilities},
+    region_networking::messages::OnSpawnThing,
+    things::components::class_ref,
+    this::{components::*, messages::*},
+};
+
+mod shared;
+
+/// The number of slots in chests whose class doesn't set chest_size.
+pub const DEFAULT_CHEST_SIZE: u32 = 16;
+
+#[main]
+fn main() {
+    shared::init_shared();
+
+    // give new chests their empty slots, sized by their class
+    spawn_query((chest_coords(), class_ref())).bind(move |entities| {
+        for (e, (_coords, class)) in entities {
+            if !entity::has_component(class, is_chest()) {
+                continue;
+            }
+
+            // the contents may have already been restored from a save
+            if entity::has_component(e, container_classes()) {
+                continue;
+            }
+
+            let size = entity::get_component(class, chest_size()).unwrap_or(DEFAULT_CHEST_SIZE);
+            let size = size as usize;
+            entity::add_components(
+                e,
+                Entity::new()
+                    .with(container_classes(), vec![EntityId::null(); size])
+                    .with(container_counts(), vec![0; size])
+                    .with(container_durabilities(), vec![0; size]),
+            );
+        }
+    });
+
+    OnSpawnThing::subscribe(move |source, spawn| {
+        if source.local().is_none() {
+            return;
+        }
+
+        if let Some(coords) = entity::get_component(spawn.thing, chest_coords()) {
+            UpdateChestCoords::new(spawn.thing, coords)
+                .send_client_targeted_reliable(spawn.player_uid);
+        }
+    });
+}
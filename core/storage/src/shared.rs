@@ -0,0 +1,90 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use ambient_api::prelude::*;
+
+use crate::packages::{
+    map::components::{blocks_movement, chunk, chunk_tile_refs, position},
+    this::components::*,
+};
+use flowerpot_common::{init_map, CHUNK_SIZE};
+
+/// Attaches a chest to the tile at its coordinates in a loaded chunk.
+fn attach_chest(e: EntityId, coords: IVec2, tiles: &[EntityId]) {
+    let fine = coords.rem_euclid(IVec2::splat(CHUNK_SIZE as i32));
+    let tile_idx = (fine.y * CHUNK_SIZE as i32 + fine.x) as usize;
+
+    let Some(tile) = tiles.get(tile_idx).copied() else {
+        eprintln!("chest {} has no tile {}", e, tile_idx);
+        return;
+    };
+
+    entity::add_components(
+        e,
+        Entity::new()
+            .with(chest_tile(), tile)
+            .with(position(), coords.as_vec2() + 0.5),
+    );
+
+    entity::add_component(tile, chest_occupant(), e);
+    entity::add_component(tile, blocks_movement(), ());
+}
+
+pub fn init_shared() {
+    let chunks = init_map(chunk());
+
+    // chests whose chunks haven't been loaded yet, by chunk position
+    let pending = Arc::new(Mutex::new(HashMap::<IVec2, Vec<EntityId>>::new()));
+
+    spawn_query(chest_coords()).bind({
+        let chunks = chunks.clone();
+        let pending = pending.clone();
+        move |entities| {
+            let chunks = chunks.lock().unwrap();
+            let mut pending = pending.lock().unwrap();
+            for (e, coords) in entities {
+                let xy = coords.div_euclid(IVec2::splat(CHUNK_SIZE as i32));
+                let tiles = chunks
+                    .get(&xy)
+                    .and_then(|chunk| entity::get_component(*chunk, chunk_tile_refs()));
+
+                match tiles {
+                    Some(tiles) => attach_chest(e, coords, &tiles),
+                    None => pending.entry(xy).or_default().push(e),
+                }
+            }
+        }
+    });
+
+    spawn_query((chunk(), chunk_tile_refs())).bind(move |entities| {
+        let mut pending = pending.lock().unwrap();
+        for (_chunk, (xy, tiles)) in entities {
+            for e in pending.remove(&xy).unwrap_or_default() {
+                // the chest may have been despawned while it was waiting
+                if let Some(coords) = entity::get_component(e, chest_coords()) {
+                    attach_chest(e, coords, &tiles);
+                }
+            }
+        }
+    });
+
+    // despawn chests along with the tiles they're on
+    despawn_query(chest_occupant()).bind(move |entities| {
+        for (_tile, occupant) in entities {
+            if !occupant.is_null() && entity::exists(occupant) {
+                entity::despawn_recursive(occupant);
+            }
+        }
+    });
+
+    despawn_query(chest_tile()).bind(move |entities| {
+        for (e, tile) in entities {
+            if entity::get_component(tile, chest_occupant()) == Some(e) {
+                entity::set_component(tile, chest_occupant(), EntityId::null());
+                entity::remove_component(tile, blocks_movement());
+            }
+        }
+    });
+}
@@ -5,9 +5,13 @@ use std::{
 
 use ambient_api::{
     core::{
+        primitives::components::quad,
         procedurals::components::procedural_mesh,
-        rendering::components::{color, pbr_material_from_url},
-        transform::concepts::make_transformable,
+        rendering::components::{color, pbr_material_from_url, transparency_group},
+        transform::{
+            components::{scale, translation},
+            concepts::make_transformable,
+        },
     },
     mesh::{self, Vertex},
     prelude::*,
@@ -20,7 +24,7 @@ use rapier3d::{
 };
 
 use packages::{
-    map::components::{chunk, chunk_tile_index, in_chunk},
+    map::components::{chunk, chunk_tile_index, chunk_tile_refs, in_chunk},
     this::{assets, components::*, messages::*},
 };
 
@@ -30,14 +34,22 @@ mod shared;
 
 type ChunkMeshes = Arc<Mutex<HashMap<IVec2, (EntityId, TriMesh)>>>;
 
+/// The color of the water's surface.
+pub const WATER_COLOR: Vec4 = Vec4::new(0.1, 0.35, 0.6, 0.7);
+
 #[main]
 fn main() {
-    shared::init_shared_terrain();
+    run_async(async_main());
+}
+
+async fn async_main() {
+    let params = shared::init_shared_terrain().await;
 
     let meshes = ChunkMeshes::default();
 
-    spawn_query((chunk(), heightmap())).bind(move |entities| {
-        for (e, (chunk_xy, altitudes)) in entities {
+    spawn_query((chunk(), heightmap(), chunk_tile_refs())).bind(move |entities| {
+        let params = shared::WorldGenParams::get().unwrap_or(params);
+        for (e, (chunk_xy, altitudes, tiles)) in entities {
             let vertex_num = CHUNK_SIZE * CHUNK_SIZE * 6;
 
             let mut positions = Vec::with_capacity(vertex_num);
@@ -50,8 +62,7 @@ fn main() {
                 let altitudes_dim = CHUNK_SIZE + 1;
                 let (x, y) = v;
                 let idx = y * altitudes_dim + x;
-                let altitude = altitudes[idx];
-                let z = altitude as f32 / 4.0;
+                let z = params.dequantize(altitudes[idx]);
                 let chunk_offset = chunk_xy * (CHUNK_SIZE as i32);
                 let xy = vec2(x as f32, y as f32) + chunk_offset.as_vec2();
                 xy.extend(z)
@@ -86,12 +97,20 @@ fn main() {
                 indices.extend([voff, voff + 1, voff + 2]);
             };
 
-            for y in 0..CHUNK_SIZE {
-                for x in 0..CHUNK_SIZE {
-                    let v1 = ((x, y), vec2(0.0, 0.0));
-                    let v2 = ((x + 1, y), vec2(1.0, 0.0));
-                    let v3 = ((x, y + 1), vec2(0.0, 1.0));
-                    let v4 = ((x + 1, y + 1), vec2(1.0, 1.0));
+            // each tile is always made of exactly six vertices so that
+            // tile highlights can look up their vertices by tile index
+            for (idx, tile) in tiles.into_iter().enumerate() {
+                let local = shared::tile_local(idx);
+                let (x, y) = (local.x as usize, local.y as usize);
+                let v1 = ((x, y), vec2(0.0, 0.0));
+                let v2 = ((x + 1, y), vec2(1.0, 0.0));
+                let v3 = ((x, y + 1), vec2(0.0, 1.0));
+                let v4 = ((x + 1, y + 1), vec2(1.0, 1.0));
+
+                if shared::get_cut_direction(tile, chunk_xy, &altitudes, local, &params) {
+                    add_tri(v1, v2, v4);
+                    add_tri(v1, v4, v3);
+                } else {
                     add_tri(v1, v2, v3);
                     add_tri(v3, v2, v4);
                 }
@@ -109,6 +128,33 @@ fn main() {
         }
     });
 
+    // cover each chunk with any water in it with a flat water surface
+    spawn_query((chunk(), heightmap())).bind(move |entities| {
+        let params = shared::WorldGenParams::get().unwrap_or(params);
+        for (e, (chunk_xy, altitudes)) in entities {
+            if altitudes
+                .iter()
+                .all(|height| params.dequantize(*height) >= params.sea_level)
+            {
+                continue;
+            }
+
+            let chunk_size = CHUNK_SIZE as f32;
+            let center = (chunk_xy.as_vec2() + 0.5) * chunk_size;
+
+            let water = Entity::new()
+                .with_merge(make_transformable())
+                .with(quad(), ())
+                .with(translation(), center.extend(params.sea_level))
+                .with(scale(), vec3(chunk_size, chunk_size, 1.0))
+                .with(color(), WATER_COLOR)
+                .with(transparency_group(), 0)
+                .spawn();
+
+            entity::add_child(e, water);
+        }
+    });
+
     spawn_query((
         mesh_positions(),
         mesh_normals(),
@@ -302,6 +348,9 @@ fn main() {
                     indices: &[0, 1, 2, 3, 4, 5],
                 });
 
+                // highlights may be tinted by whoever spawned them
+                let tint = entity::get_component(e, color()).unwrap_or(Vec4::ONE);
+
                 entity::add_components(
                     e,
                     make_transformable()
@@ -310,7 +359,7 @@ fn main() {
                             pbr_material_from_url(),
                             assets::url("pipeline.toml/1/mat.json"),
                         )
-                        .with(color(), Vec4::ONE),
+                        .with(color(), tint),
                 );
             }
         });
@@ -4,5 +4,12 @@ mod shared;
 
 #[main]
 fn main() {
-    shared::init_shared_terrain();
+    // the parameters may have already been configured or restored from a save
+    if shared::WorldGenParams::get().is_none() {
+        shared::WorldGenParams::default().set();
+    }
+
+    run_async(async {
+        shared::init_shared_terrain().await;
+    });
 }
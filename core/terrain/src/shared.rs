@@ -1,31 +1,262 @@
 use ambient_api::{glam::DVec2, prelude::*};
 use flowerpot_common::CHUNK_SIZE;
-use noise::{Fbm, NoiseFn};
+use noise::{Fbm, MultiFractal, NoiseFn};
 
 use crate::packages::{
-    map::components::{chunk, in_chunk, position},
+    map::components::{chunk, chunk_tile_refs, in_chunk, position},
     this::components::*,
 };
 
-pub const RESOLUTION: f64 = 1.0 / 256.0;
+/// The overall shape of the generated terrain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TerrainMode {
+    /// Unbounded noise in all directions.
+    Continuous,
 
-pub fn init_shared_terrain() {
-    let noise = Fbm::<noise::OpenSimplex>::new(0);
+    /// An island centered on the origin that falls off into the sea.
+    Island,
+}
+
+impl TerrainMode {
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::Continuous),
+            1 => Some(Self::Island),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> u8 {
+        match self {
+            Self::Continuous => 0,
+            Self::Island => 1,
+        }
+    }
+}
+
+/// The heuristic used to pick each tile's cut direction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CutHeuristic {
+    Uniform,
+    Checkerboard,
+    SlopeMinimizing,
+}
+
+impl CutHeuristic {
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::Uniform),
+            1 => Some(Self::Checkerboard),
+            2 => Some(Self::SlopeMinimizing),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> u8 {
+        match self {
+            Self::Uniform => 0,
+            Self::Checkerboard => 1,
+            Self::SlopeMinimizing => 2,
+        }
+    }
+}
+
+/// The world-generation parameters shared between the server and all clients.
+///
+/// Stored in synchronized resources so that clients derive the same heightmaps
+/// as the server.
+#[derive(Clone, Copy, Debug)]
+pub struct WorldGenParams {
+    pub seed: u32,
+    pub octaves: u32,
+    pub frequency: f64,
+    pub amplitude: f32,
+    pub height_quantization: f32,
+    pub mode: TerrainMode,
+    pub island_radius: f32,
+    pub sea_level: f32,
+    pub cut_heuristic: CutHeuristic,
+}
+
+impl Default for WorldGenParams {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            octaves: Fbm::<noise::OpenSimplex>::DEFAULT_OCTAVE_COUNT as u32,
+            frequency: 1.0 / 256.0,
+            amplitude: 16.0,
+            height_quantization: 4.0,
+            mode: TerrainMode::Island,
+            island_radius: 192.0,
+            sea_level: 0.0,
+            cut_heuristic: CutHeuristic::SlopeMinimizing,
+        }
+    }
+}
+
+impl WorldGenParams {
+    pub fn get() -> Option<Self> {
+        let resources = entity::synchronized_resources();
+        Some(Self {
+            seed: entity::get_component(resources, terrain_seed())?,
+            octaves: entity::get_component(resources, terrain_octaves())?,
+            frequency: entity::get_component(resources, terrain_frequency())?,
+            amplitude: entity::get_component(resources, terrain_amplitude())?,
+            height_quantization: entity::get_component(resources, terrain_height_quantization())?,
+            mode: TerrainMode::from_id(entity::get_component(resources, terrain_mode())?)?,
+            island_radius: entity::get_component(resources, terrain_island_radius())?,
+            sea_level: entity::get_component(resources, terrain_sea_level())?,
+            cut_heuristic: CutHeuristic::from_id(entity::get_component(
+                resources,
+                terrain_cut_heuristic(),
+            )?)?,
+        })
+    }
+
+    pub fn set(&self) {
+        entity::add_components(
+            entity::synchronized_resources(),
+            Entity::new()
+                .with(terrain_seed(), self.seed)
+                .with(terrain_octaves(), self.octaves)
+                .with(terrain_frequency(), self.frequency)
+                .with(terrain_amplitude(), self.amplitude)
+                .with(terrain_height_quantization(), self.height_quantization)
+                .with(terrain_mode(), self.mode.id())
+                .with(terrain_island_radius(), self.island_radius)
+                .with(terrain_sea_level(), self.sea_level)
+                .with(terrain_cut_heuristic(), self.cut_heuristic.id()),
+        );
+    }
+
+    /// Waits until the parameters have been set (or synchronized from the server).
+    pub async fn wait() -> Self {
+        loop {
+            if let Some(params) = Self::get() {
+                return params;
+            }
+
+            sleep(0.1).await;
+        }
+    }
+
+    pub fn make_noise(&self) -> Fbm<noise::OpenSimplex> {
+        Fbm::new(self.seed).set_octaves(self.octaves as usize)
+    }
+
+    /// Samples the quantized height of a tile corner at the given map position.
+    pub fn sample(&self, noise: &impl NoiseFn<f64, 2>, xy: IVec2) -> i16 {
+        let sample = DVec2::new(xy.x as f64, xy.y as f64) * self.frequency;
+        let mut height = noise.get([sample.x, sample.y]);
+
+        if self.mode == TerrainMode::Island {
+            height = (height + self.island_falloff(xy)) / 2.0;
+        }
+
+        let steps = self.amplitude as f64 * self.height_quantization as f64;
+        (height * steps).round() as i16
+    }
+
+    /// Returns the radial falloff of the island at the given map position.
+    ///
+    /// This is 1.0 at the center of the island, -1.0 at its radius, and keeps
+    /// dropping off a little further past that so that the island is always
+    /// surrounded by sea.
+    pub fn island_falloff(&self, xy: IVec2) -> f64 {
+        let radius = self.island_radius.max(1.0) as f64;
+        let distance = DVec2::new(xy.x as f64, xy.y as f64).length() / radius;
+        1.0 - 2.0 * distance.min(1.5).powi(2)
+    }
+
+    /// Converts a quantized heightmap value into an altitude.
+    pub fn dequantize(&self, height: i16) -> f32 {
+        height as f32 / self.height_quantization
+    }
+
+    /// Calculates how far below sea level the tile at the given local position
+    /// in a chunk's heightmap is, using the average altitude of its corners.
+    ///
+    /// Negative for tiles that are above sea level.
+    pub fn water_depth(&self, altitudes: &[i16], local: UVec2) -> f32 {
+        let sum: f32 = tile_corners(altitudes, local)
+            .into_iter()
+            .map(|height| self.dequantize(height))
+            .sum();
+
+        self.sea_level - sum / 4.0
+    }
+
+    /// Tests if the tile at the given local position in a chunk's heightmap is
+    /// underwater.
+    pub fn is_water_tile(&self, altitudes: &[i16], local: UVec2) -> bool {
+        self.water_depth(altitudes, local) > 0.0
+    }
+
+    /// Picks the cut direction of a tile in a chunk using the cut heuristic.
+    pub fn cut_direction(&self, chunk_xy: IVec2, altitudes: &[i16], local: UVec2) -> bool {
+        let checkerboard = || {
+            let xy = chunk_xy * CHUNK_SIZE as i32 + local.as_ivec2();
+            (xy.x + xy.y).rem_euclid(2) == 1
+        };
+
+        match self.cut_heuristic {
+            CutHeuristic::Uniform => false,
+            CutHeuristic::Checkerboard => checkerboard(),
+            CutHeuristic::SlopeMinimizing => {
+                let [v1, v2, v3, v4] = tile_corners(altitudes, local);
+                let forward = (v1 - v4).abs();
+                let backward = (v2 - v3).abs();
+                if forward == backward {
+                    checkerboard()
+                } else {
+                    forward < backward
+                }
+            }
+        }
+    }
+}
+
+/// Returns the quantized heights of a tile's corners, in the order X- Y-,
+/// X+ Y-, X- Y+, and X+ Y+.
+pub fn tile_corners(altitudes: &[i16], local: UVec2) -> [i16; 4] {
+    let dim = CHUNK_SIZE + 1;
+    let x = local.x as usize;
+    let y = local.y as usize;
+    [(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)].map(|(x, y)| altitudes[y * dim + x])
+}
+
+/// Gets the cut direction of a tile, falling back on the cut heuristic if the
+/// tile hasn't been assigned one yet.
+pub fn get_cut_direction(
+    tile: EntityId,
+    chunk_xy: IVec2,
+    altitudes: &[i16],
+    local: UVec2,
+    params: &WorldGenParams,
+) -> bool {
+    entity::get_component(tile, cut_direction())
+        .unwrap_or_else(|| params.cut_direction(chunk_xy, altitudes, local))
+}
+
+/// Initializes the terrain systems shared between the server and client.
+///
+/// Waits for the world-generation parameters to become available, then
+/// returns them.
+pub async fn init_shared_terrain() -> WorldGenParams {
+    let params = WorldGenParams::wait().await;
 
     spawn_query(chunk()).bind(move |entities| {
+        let params = WorldGenParams::get().unwrap_or(params);
+        let noise = params.make_noise();
+
         for (e, chunk_xy) in entities {
             let altitudes_dim = CHUNK_SIZE + 1;
             let mut altitudes = Vec::with_capacity(altitudes_dim * altitudes_dim);
             let altitudes_dim = altitudes_dim as i32;
             for y in 0..altitudes_dim {
                 for x in 0..altitudes_dim {
-                    let x = x + (CHUNK_SIZE as i32 * chunk_xy.x);
-                    let y = y + (CHUNK_SIZE as i32 * chunk_xy.y);
-                    let sample = DVec2::new(x as f64, y as f64);
-                    let sample = sample * RESOLUTION;
-                    let new_altitude = noise.get([sample.x, sample.y]);
-                    let new_altitude = (new_altitude * 64.0).round() as i16;
-                    altitudes.push(new_altitude);
+                    let xy = ivec2(x, y) + chunk_xy * CHUNK_SIZE as i32;
+                    altitudes.push(params.sample(&noise, xy));
                 }
             }
 
@@ -33,6 +264,30 @@ pub fn init_shared_terrain() {
         }
     });
 
+    spawn_query((chunk(), chunk_tile_refs(), heightmap())).bind(move |entities| {
+        let params = WorldGenParams::get().unwrap_or(params);
+        for (_e, (chunk_xy, tiles, altitudes)) in entities {
+            for (idx, tile) in tiles.into_iter().enumerate() {
+                let local = tile_local(idx);
+
+                if !entity::has_component(tile, cut_direction()) {
+                    let cut = params.cut_direction(chunk_xy, &altitudes, local);
+                    entity::add_component(tile, cut_direction(), cut);
+                }
+
+                let depth = params.water_depth(&altitudes, local);
+                if depth > 0.0 {
+                    entity::add_components(
+                        tile,
+                        Entity::new()
+                            .with(is_water(), ())
+                            .with(water_depth(), depth),
+                    );
+                }
+            }
+        }
+    });
+
     spawn_query((position(), in_chunk())).bind(move |entities| {
         for (e, (position, in_chunk)) in entities {
             update_altitude(e, position, in_chunk);
@@ -46,6 +301,13 @@ pub fn init_shared_terrain() {
                 update_altitude(e, position, in_chunk);
             }
         });
+
+    params
+}
+
+/// Converts a tile's index in its chunk into its local position.
+pub fn tile_local(idx: usize) -> UVec2 {
+    uvec2((idx % CHUNK_SIZE) as u32, (idx / CHUNK_SIZE) as u32)
 }
 
 pub fn update_altitude(e: EntityId, position: Vec2, in_chunk: EntityId) {
@@ -59,45 +321,65 @@ pub fn update_altitude(e: EntityId, position: Vec2, in_chunk: EntityId) {
         return;
     };
 
-    if let Some(new_altitude) = calculate_altitude(local_pos, &altitudes) {
+    let Some(params) = WorldGenParams::get() else {
+        return;
+    };
+
+    let coarse_pos = local_pos.floor().as_ivec2();
+    if !is_in_chunk(coarse_pos) {
+        return;
+    }
+
+    let local = coarse_pos.as_uvec2();
+    let tile_idx = local.y as usize * CHUNK_SIZE + local.x as usize;
+    let cut = match entity::get_component(in_chunk, chunk_tile_refs()) {
+        Some(tiles) => get_cut_direction(tiles[tile_idx], chunk_xy, &altitudes, local, &params),
+        None => params.cut_direction(chunk_xy, &altitudes, local),
+    };
+
+    if let Some(new_altitude) = calculate_altitude(local_pos, &altitudes, cut, &params) {
         entity::add_component(e, altitude(), new_altitude);
     }
 }
 
-pub fn calculate_altitude(local_pos: Vec2, altitudes: &[i16]) -> Option<f32> {
+fn is_in_chunk(local: IVec2) -> bool {
+    local.x >= 0 && local.y >= 0 && local.x < CHUNK_SIZE as i32 && local.y < CHUNK_SIZE as i32
+}
+
+/// Calculates the altitude of a position within a chunk, given the cut
+/// direction of the tile it's on.
+pub fn calculate_altitude(
+    local_pos: Vec2,
+    altitudes: &[i16],
+    cut: bool,
+    params: &WorldGenParams,
+) -> Option<f32> {
     let coarse_pos = local_pos.floor().as_ivec2();
     let fine_pos = local_pos - coarse_pos.as_vec2();
 
-    if coarse_pos.x < 0
-        || coarse_pos.y < 0
-        || coarse_pos.x >= CHUNK_SIZE as i32
-        || coarse_pos.y >= CHUNK_SIZE as i32
-    {
+    if !is_in_chunk(coarse_pos) {
         return None;
     }
 
-    let get_altitude = |x, y| {
-        let x = x as usize;
-        let y = y as usize;
-        let idx = y * (CHUNK_SIZE + 1) + x;
-        altitudes[idx] as f32 / 4.0
-    };
-
-    let IVec2 { x, y } = coarse_pos;
-    let v1 = get_altitude(x, y);
-    let v2 = get_altitude(x + 1, y);
-    let v3 = get_altitude(x, y + 1);
-    let v4 = get_altitude(x + 1, y + 1);
+    let [v1, v2, v3, v4] =
+        tile_corners(altitudes, coarse_pos.as_uvec2()).map(|height| params.dequantize(height));
 
-    let (base, flip_x, dx, flip_y, dy) = if fine_pos.x + fine_pos.y < 1.0 {
-        (v1, false, v2 - v1, false, v3 - v1)
+    let Vec2 { x, y } = fine_pos;
+    let altitude = if cut {
+        // cut from v1 to v4
+        if x >= y {
+            v1 + x * (v2 - v1) + y * (v4 - v2)
+        } else {
+            v1 + y * (v3 - v1) + x * (v4 - v3)
+        }
     } else {
-        (v4, true, v3 - v4, true, v2 - v4)
+        // cut from v2 to v3
+        if x + y < 1.0 {
+            v1 + x * (v2 - v1) + y * (v3 - v1)
+        } else {
+            v4 + (1.0 - x) * (v3 - v4) + (1.0 - y) * (v2 - v4)
+        }
     };
 
-    let x = if flip_x { 1.0 - fine_pos.x } else { fine_pos.x };
-    let y = if flip_y { 1.0 - fine_pos.y } else { fine_pos.y };
-    let altitude = base + x * dx + y * dy;
-
     Some(altitude)
 }
@@ -13,16 +13,52 @@ use ambient_api::{
 mod shared;
 
 use packages::{
-    actions::messages::{PerformCraftingAction, PerformSwap, PerformTileAction},
-    crops::components::medium_crop_occupant,
+    actions::messages::{
+        ActionRejected, CraftingResult, PerformCraftingAction, PerformSwap, PerformTileAction,
+    },
+    crops::components::{large_crop_occupant, medium_crop_occupant, small_crop_occupant},
     fauna::components::{is_mod_loaded as is_fauna_loaded, pitch, yaw},
+    items::{
+        components::{
+            class, container_classes, container_counts, inventory_ref, map_item_selection_ref,
+            open_container_ref, quantity,
+        },
+        messages::{
+            CloseContainer, DropHeldItem, HoldInventoryItem, MoveInventoryItems, PickUpMapItem,
+            StoreHeldItems, StoreInventoryItems, TakeContainerItems,
+        },
+    },
     map::components::{
         chunk, chunk_tile_index, chunk_tile_refs, in_chunk, is_mod_loaded as is_map_loaded,
         position,
     },
+    nameplate::components::name,
+    region_networking::components::remote_entity,
+    storage::components::chest_occupant,
     this::{components::*, messages::*},
 };
 
+/// How long in seconds to display notices about the player's actions.
+const NOTICE_DISPLAY_TIME: f32 = 3.0;
+
+/// The number of keys that hold items from the first inventory slots.
+const HOTBAR_SIZE: usize = 8;
+
+/// The keys that hold the item in each hotbar slot.
+const HOTBAR_KEYS: [KeyCode; HOTBAR_SIZE] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+];
+
+/// The number of slots in each row of the inventory window.
+const INVENTORY_COLUMNS: usize = 8;
+
 #[main]
 fn main() {
     run_async(async_main());
@@ -54,7 +90,15 @@ fn App(hooks: &mut Hooks) -> Element {
 
 #[element_component]
 fn GameUI(_hooks: &mut Hooks) -> Element {
-    Group::el([Crosshair::el(), Controls::el(), Chat::el(), Status::el()])
+    Group::el([
+        Crosshair::el(),
+        Controls::el(),
+        Chat::el(),
+        Status::el(),
+        Hotbar::el(),
+        InventoryWindow::el(),
+        ContainerWindow::el(),
+    ])
 }
 
 // TODO: either yoink a better crosshair from AFPS when it has one or make one ourselves and share
@@ -81,6 +125,11 @@ fn Crosshair(hooks: &mut Hooks) -> Element {
 #[element_component]
 fn Controls(hooks: &mut Hooks) -> Element {
     let (locked, set_locked) = hooks.use_state(false);
+    let (inventory_open, _) = hooks.use_entity_component(entity::resources(), inventory_open());
+    let inventory_open = inventory_open.unwrap_or(false);
+    let (open_container, _) =
+        hooks.use_entity_component(player::get_local(), open_container_ref());
+    let container_open = open_container.is_some();
 
     hooks.consume_context::<Focus>();
 
@@ -89,11 +138,22 @@ fn Controls(hooks: &mut Hooks) -> Element {
         move |_| {
             if locked {
                 let (delta, input) = input::get_delta();
-                if delta.keys.contains(&KeyCode::Escape) {
+                if container_open {
+                    // free the cursor so that the open container can be clicked
+                    input::set_cursor_lock(false);
+                    input::set_cursor_visible(true);
+                    set_locked(false);
+                } else if delta.keys.contains(&KeyCode::Escape) {
                     eprintln!("escaping!");
                     input::set_cursor_lock(false);
                     input::set_cursor_visible(true);
                     set_locked(false);
+                } else if delta.keys.contains(&KeyCode::Tab) {
+                    // free the cursor so that the inventory can be clicked
+                    input::set_cursor_lock(false);
+                    input::set_cursor_visible(true);
+                    set_locked(false);
+                    entity::add_component(entity::resources(), inventory_open(), true);
                 } else {
                     update_controls(delta, input);
                 }
@@ -103,7 +163,7 @@ fn Controls(hooks: &mut Hooks) -> Element {
 
     ClickArea::new(WindowSized::el([]))
         .on_mouse_down(move |_, _, _| {
-            if !locked {
+            if !locked && !inventory_open && !container_open {
                 eprintln!("clicked!");
                 input::set_cursor_lock(true);
                 input::set_cursor_visible(false);
@@ -161,17 +221,43 @@ fn update_controls(delta: InputDelta, input: Input) {
         PerformSwap::new().send_local_broadcast(false);
     }
 
+    // number keys hold hotbar items in the right hand, or the left with shift
+    let right = !input.keys.contains(&KeyCode::LShift);
+    for (slot, key) in HOTBAR_KEYS.iter().enumerate() {
+        if delta.keys.contains(key) {
+            HoldInventoryItem::new(slot as u8, right).send_server_reliable();
+        }
+    }
+
+    if delta.keys.contains(&KeyCode::G) {
+        DropHeldItem::new(right).send_server_reliable();
+    }
+
     if delta.mouse_buttons.contains(&MouseButton::Left) {
-        if let Some(selected) = entity::get_component(player::get_local(), tile_selection_ref()) {
+        // map items lie on top of tiles, so they're picked up first
+        let map_item = entity::get_component(player::get_local(), map_item_selection_ref());
+        let selected_tile = entity::get_component(player::get_local(), tile_selection_ref());
+        if let Some(thing) = map_item.and_then(|e| entity::get_component(e, remote_entity())) {
+            PickUpMapItem::new(thing).send_server_reliable();
+        } else if let Some(selected) = selected_tile {
             let chunk_ref = entity::get_component(selected, in_chunk()).unwrap();
             let chunk_pos = entity::get_component(chunk_ref, chunk()).unwrap();
             let tile_idx = entity::get_component(selected, chunk_tile_index()).unwrap();
             let tiles = entity::get_component(chunk_ref, chunk_tile_refs()).unwrap();
             let tile = tiles[tile_idx as usize];
 
-            let on_occupant = entity::get_component(tile, medium_crop_occupant())
-                .unwrap_or_default()
-                != EntityId::null();
+            let on_occupant = [
+                chest_occupant(),
+                small_crop_occupant(),
+                medium_crop_occupant(),
+                large_crop_occupant(),
+            ]
+            .into_iter()
+            .any(|occupant| {
+                !entity::get_component(tile, occupant)
+                    .unwrap_or_default()
+                    .is_null()
+            });
 
             PerformTileAction {
                 chunk_pos,
@@ -308,11 +394,245 @@ fn Status(hooks: &mut Hooks) -> Element {
         .map(|coords| format!("Map position: ({:.1}, {:.1})", coords.x, coords.y))
         .unwrap_or_default();
 
-    FlowColumn::el([Text::el(coords)])
+    let (notice, set_notice) = hooks.use_state("".to_string());
+    let notice_count = hooks.use_ref_with(|_| 0u32);
+
+    // shows a notice for a while, unless a newer one replaces it
+    let show_notice = move |content: String| {
+        let count = {
+            let mut notice_count = notice_count.lock();
+            *notice_count += 1;
+            *notice_count
+        };
+
+        set_notice(content);
+
+        let notice_count = notice_count.clone();
+        let set_notice = set_notice.clone();
+        run_async(async move {
+            sleep(NOTICE_DISPLAY_TIME).await;
+            if *notice_count.lock() == count {
+                set_notice("".to_string());
+            }
+        });
+    };
+
+    hooks.use_module_message({
+        let show_notice = show_notice.clone();
+        move |_, _, data: &ActionRejected| {
+            show_notice(data.reason.clone());
+        }
+    });
+
+    hooks.use_module_message(move |_, _, data: &CraftingResult| {
+        if !data.success {
+            show_notice(data.reason.clone());
+        }
+    });
+
+    let (map_item, _) = hooks.use_entity_component(player::get_local(), map_item_selection_ref());
+
+    let mut lines = vec![Text::el(coords)];
+    if let Some(map_item) = map_item {
+        let map_item_class = entity::get_component(map_item, class()).unwrap_or_default();
+        let count = entity::get_component(map_item, quantity()).unwrap_or(0);
+        lines.push(Text::el(format!(
+            "Click to pick up: {}",
+            slot_label(map_item_class, count)
+        )));
+    }
+
+    if !notice.is_empty() {
+        lines.push(Text::el(notice).error_text_style());
+    }
+
+    FlowColumn::el(lines)
         .with_padding_even(10.0)
         .with_background(Vec3::ZERO.extend(0.8))
 }
 
+/// Gets the item classes and counts in each slot of the local player's inventory.
+fn use_inventory(hooks: &mut Hooks) -> (Vec<EntityId>, Vec<u32>) {
+    let (inventory, _) = hooks.use_entity_component(player::get_local(), inventory_ref());
+    let inventory = inventory.unwrap_or_default();
+    let (classes, _) = hooks.use_entity_component(inventory, container_classes());
+    let (counts, _) = hooks.use_entity_component(inventory, container_counts());
+    (classes.unwrap_or_default(), counts.unwrap_or_default())
+}
+
+/// Describes the contents of an inventory slot.
+fn slot_label(class: EntityId, count: u32) -> String {
+    if class.is_null() {
+        return "-".to_string();
+    }
+
+    let name = entity::get_component(class, name()).unwrap_or_default();
+    if count > 1 {
+        format!("{} x{}", name, count)
+    } else {
+        name
+    }
+}
+
+#[element_component]
+fn Hotbar(hooks: &mut Hooks) -> Element {
+    let (classes, counts) = use_inventory(hooks);
+
+    let slots = classes
+        .into_iter()
+        .zip(counts)
+        .take(HOTBAR_SIZE)
+        .enumerate()
+        .map(|(idx, (class, count))| {
+            Text::el(format!("{}: {}", idx + 1, slot_label(class, count)))
+        });
+
+    let hotbar = FlowColumn::el(slots)
+        .with(space_between_items(), STREET)
+        .with_padding_even(10.0)
+        .with_background(Vec3::ZERO.extend(0.8));
+
+    WindowSized::el([Dock::el([hotbar.with(docking(), Docking::Right)])])
+}
+
+#[element_component]
+fn InventoryWindow(hooks: &mut Hooks) -> Element {
+    let (open, _) = hooks.use_entity_component(entity::resources(), inventory_open());
+    let (classes, counts) = use_inventory(hooks);
+    let (open_container, _) =
+        hooks.use_entity_component(player::get_local(), open_container_ref());
+
+    // the first slot clicked is moved onto the second
+    let (selected, set_selected) = hooks.use_state(None::<usize>);
+
+    // the container window shows the inventory while a container is open
+    if !open.unwrap_or(false) || open_container.is_some() {
+        return Element::new();
+    }
+
+    let slots: Vec<_> = classes
+        .into_iter()
+        .zip(counts)
+        .enumerate()
+        .map(|(idx, (class, count))| {
+            let set_selected = set_selected.clone();
+            Button::new(slot_label(class, count), move |_| match selected {
+                Some(from) => {
+                    MoveInventoryItems::new(from as u8, idx as u8).send_server_reliable();
+                    set_selected(None);
+                }
+                None => set_selected(Some(idx)),
+            })
+            .toggled(selected == Some(idx))
+            .el()
+            .with(min_width(), 100.0)
+        })
+        .collect();
+
+    let rows = slots
+        .chunks(INVENTORY_COLUMNS)
+        .map(|row| FlowRow::el(row.to_vec()).with(space_between_items(), STREET));
+
+    let close = Button::new("Close", move |_| {
+        set_selected(None);
+        entity::add_component(entity::resources(), inventory_open(), false);
+    })
+    .el();
+
+    let hint = "Click two slots to move items between them. The number keys hold the \
+        items in the first slots.";
+
+    let window = FlowColumn::el(
+        [Text::el("Inventory").header_style(), Text::el(hint)]
+            .into_iter()
+            .chain(rows)
+            .chain([close]),
+    )
+    .with(space_between_items(), STREET)
+    .with_padding_even(20.0)
+    .with_background(app_background_color().into());
+
+    WindowSized::el([Dock::el([window.with(docking(), Docking::Fill)])])
+}
+
+/// Lays out buttons for container slots in rows.
+fn slot_rows(
+    classes: Vec<EntityId>,
+    counts: Vec<u32>,
+    on_click: impl Fn(usize) + Clone + Sync + Send + 'static,
+) -> Vec<Element> {
+    let slots: Vec<_> = classes
+        .into_iter()
+        .zip(counts)
+        .enumerate()
+        .map(|(idx, (class, count))| {
+            let on_click = on_click.clone();
+            Button::new(slot_label(class, count), move |_| on_click(idx))
+                .el()
+                .with(min_width(), 100.0)
+        })
+        .collect();
+
+    slots
+        .chunks(INVENTORY_COLUMNS)
+        .map(|row| FlowRow::el(row.to_vec()).with(space_between_items(), STREET))
+        .collect()
+}
+
+#[element_component]
+fn ContainerWindow(hooks: &mut Hooks) -> Element {
+    let (open_container, _) =
+        hooks.use_entity_component(player::get_local(), open_container_ref());
+    let container = open_container.unwrap_or_default();
+    let (chest_classes, _) = hooks.use_entity_component(container, container_classes());
+    let (chest_counts, _) = hooks.use_entity_component(container, container_counts());
+    let (classes, counts) = use_inventory(hooks);
+
+    if open_container.is_none() {
+        return Element::new();
+    }
+
+    let container_rows = slot_rows(
+        chest_classes.unwrap_or_default(),
+        chest_counts.unwrap_or_default(),
+        |slot| TakeContainerItems::new(slot as u8).send_server_reliable(),
+    );
+
+    let inventory_rows = slot_rows(classes, counts, |slot| {
+        StoreInventoryItems::new(slot as u8).send_server_reliable()
+    });
+
+    let store_held = FlowRow::el([
+        Button::new("Store left hand", |_| {
+            StoreHeldItems::new(false).send_server_reliable()
+        })
+        .el(),
+        Button::new("Store right hand", |_| {
+            StoreHeldItems::new(true).send_server_reliable()
+        })
+        .el(),
+    ])
+    .with(space_between_items(), STREET);
+
+    let close = Button::new("Close", |_| CloseContainer::new().send_server_reliable()).el();
+
+    let hint = "Click a slot to move its items between the chest and your inventory.";
+
+    let window = FlowColumn::el(
+        [Text::el("Chest").header_style(), Text::el(hint)]
+            .into_iter()
+            .chain(container_rows)
+            .chain([Text::el("Inventory").header_style()])
+            .chain(inventory_rows)
+            .chain([store_held, close]),
+    )
+    .with(space_between_items(), STREET)
+    .with_padding_even(20.0)
+    .with_background(app_background_color().into());
+
+    WindowSized::el([Dock::el([window.with(docking(), Docking::Fill)])])
+}
+
 #[element_component]
 fn JoinScreen(hooks: &mut Hooks) -> Element {
     use_input_request(hooks);
@@ -1,49 +1,259 @@
-use std::f64::consts::TAU;
+use std::{
+    collections::HashMap,
+    f64::consts::TAU,
+    sync::{Arc, Mutex},
+};
 
 use ambient_api::{
     core::messages::Frame,
     core::{
         app::components::main_scene,
-        rendering::components::{light_ambient, light_diffuse, sky, sun},
-        transform::{components::rotation, concepts::make_transformable},
+        primitives::components::cube,
+        rendering::components::{
+            color, fog_color, fog_density, fog_height_falloff, light_ambient, light_diffuse, sky,
+            sun,
+        },
+        transform::{
+            components::{rotation, scale, translation},
+            concepts::make_transformable,
+        },
     },
     prelude::*,
 };
 
-use crate::packages::this::components::*;
+use flowerpot_common::{ActorExt, SystemExt};
+use packages::{
+    map::components::{chunk, in_chunk},
+    this::{components::*, messages::*},
+};
 
 mod shared;
 
+/// The fog density on a clear day.
+pub const CLEAR_FOG_DENSITY: f32 = 0.001;
+
+/// The fog density while it's raining or snowing.
+pub const OVERCAST_FOG_DENSITY: f32 = 0.03;
+
+/// How much overcast weather dims the sun.
+pub const OVERCAST_DIMMING: f32 = 0.6;
+
+/// How quickly the sky changes to match the weather, per second.
+pub const WEATHER_TRANSITION_RATE: f32 = 0.2;
+
+/// The number of raindrops or snowflakes around the player at once.
+pub const PARTICLE_NUM: usize = 400;
+
+/// How far from the player particles fall, horizontally.
+pub const PARTICLE_RADIUS: f32 = 12.0;
+
+/// How high above the player particles start falling from.
+pub const PARTICLE_HEIGHT: f32 = 10.0;
+
+/// How many units per second raindrops fall.
+pub const RAIN_SPEED: f32 = 12.0;
+
+/// How many units per second snowflakes fall.
+pub const SNOW_SPEED: f32 = 1.5;
+
+/// The fraction of the difference from the server's time of day that the
+/// client's clock corrects every second.
+pub const TIME_CORRECTION_RATE: f64 = 2.0;
+
+/// The difference in hours from the server's time of day past which the
+/// client's clock jumps straight to the server's time instead.
+pub const TIME_SNAP_THRESHOLD: f64 = 0.5;
+
+/// The client's prediction of the time of day. The server only updates the
+/// time of day every few seconds, so the client keeps its own clock running
+/// in between and gradually corrects it whenever a new server time arrives.
+#[derive(Default)]
+pub struct Clock {
+    pub time: Option<f64>,
+    pub last_server_time: f64,
+
+    /// The remaining difference from the server's time to correct, in hours.
+    pub error: f64,
+}
+
+impl Clock {
+    /// Advances the clock by an amount of game hours and corrects it towards
+    /// the server's time of day. Returns the new time of day.
+    pub fn update(&mut self, server_time: f64, elapsed: f64, dt: f64) -> f64 {
+        let Some(time) = self.time else {
+            self.time = Some(server_time);
+            self.last_server_time = server_time;
+            return server_time;
+        };
+
+        let mut time = time + elapsed;
+
+        // the server's time is only exact at the moment that it's updated
+        if server_time != self.last_server_time {
+            self.last_server_time = server_time;
+
+            // the shortest way around the clock, so that midnight doesn't
+            // count as a whole day of error
+            self.error = (server_time - time + 12.0).rem_euclid(24.0) - 12.0;
+
+            if self.error.abs() > TIME_SNAP_THRESHOLD {
+                time = server_time;
+                self.error = 0.0;
+            }
+        }
+
+        let correction = self.error * (TIME_CORRECTION_RATE * dt).min(1.0);
+        self.error -= correction;
+
+        let time = (time + correction).rem_euclid(24.0);
+        self.time = Some(time);
+        time
+    }
+}
+
+/// The falling raindrops or snowflakes around the local player.
+#[derive(Default)]
+pub struct Precipitation {
+    pub kind: u8,
+    pub particles: Vec<(EntityId, Vec3)>,
+    pub elapsed: f32,
+}
+
+impl Precipitation {
+    fn random_offset(height: f32) -> Vec3 {
+        let xy = vec2(random::<f32>(), random::<f32>()) * 2.0 - 1.0;
+        (xy * PARTICLE_RADIUS).extend(height)
+    }
+
+    /// Respawns the particles for a new kind of weather.
+    pub fn set_kind(&mut self, kind: u8) {
+        if self.kind == kind {
+            return;
+        }
+
+        self.kind = kind;
+
+        for (e, _offset) in self.particles.drain(..) {
+            entity::despawn_recursive(e);
+        }
+
+        let (particle_scale, particle_color) = match kind {
+            shared::RAIN => (vec3(0.02, 0.02, 0.3), vec4(0.6, 0.7, 0.9, 1.0)),
+            shared::SNOW => (Vec3::splat(0.06), Vec4::ONE),
+            _ => return,
+        };
+
+        for _ in 0..PARTICLE_NUM {
+            let e = make_transformable()
+                .with(cube(), ())
+                .with(scale(), particle_scale)
+                .with(color(), particle_color)
+                .spawn();
+
+            let offset = Self::random_offset(random::<f32>() * PARTICLE_HEIGHT);
+            self.particles.push((e, offset));
+        }
+    }
+
+    /// Moves the particles down around the player, wrapping them back to the
+    /// top when they hit the ground.
+    pub fn update(&mut self, center: Vec3, dt: f32) {
+        let (speed, sway) = match self.kind {
+            shared::RAIN => (RAIN_SPEED, 0.0),
+            shared::SNOW => (SNOW_SPEED, 0.5),
+            _ => return,
+        };
+
+        self.elapsed += dt;
+        for (e, offset) in self.particles.iter_mut() {
+            offset.z -= speed * dt;
+            offset.x += (self.elapsed + offset.z).sin() * sway * dt;
+
+            if offset.z < 0.0 {
+                *offset = Self::random_offset(PARTICLE_HEIGHT);
+            }
+
+            entity::add_component(*e, translation(), center + *offset);
+        }
+    }
+}
+
 #[main]
 fn main() {
     let sun_entity = make_transformable()
         .with(sun(), 1.0)
         .with(light_diffuse(), Vec3::ONE * 5.0)
+        .with(fog_color(), vec3(0.6, 0.65, 0.7))
+        .with(fog_density(), CLEAR_FOG_DENSITY)
+        .with(fog_height_falloff(), 0.01)
         .with(main_scene(), ())
         .spawn();
 
     make_transformable().with(sky(), ()).spawn();
 
+    let chunks = flowerpot_common::init_map(chunk());
+
+    // updates for chunks that haven't been loaded on this client yet
+    let pending: Arc<Mutex<HashMap<IVec2, UpdateChunkWeather>>> = Default::default();
+
+    pending.on_message(move |pending, _, data: UpdateChunkWeather| {
+        match chunks.lock().unwrap().get(&data.chunk) {
+            Some(e) => {
+                entity::add_component(*e, weather(), data.weather);
+                entity::add_component(*e, temperature(), data.temperature);
+            }
+            None => {
+                pending.insert(data.chunk, data);
+            }
+        }
+    });
+
+    pending.on_event(spawn_query(chunk()), move |pending, e, position| {
+        if let Some(data) = pending.remove(&position) {
+            entity::add_component(e, weather(), data.weather);
+            entity::add_component(e, temperature(), data.temperature);
+        }
+    });
+
+    let mut precipitation = Precipitation::default();
+    let mut overcast = 0.0f32;
+    let mut clock = Clock::default();
+
     Frame::subscribe(move |_| {
+        // show the weather of the chunk that the player is standing in
+        let player = player::get_local();
+        let current_weather = entity::get_component(player, in_chunk())
+            .and_then(|chunk_entity| entity::get_component(chunk_entity, weather()))
+            .unwrap_or(shared::CLEAR);
+
+        let dt = delta_time();
+        let target_overcast = if current_weather == shared::CLEAR {
+            0.0
+        } else {
+            1.0
+        };
+
+        let step = WEATHER_TRANSITION_RATE * dt;
+        overcast += (target_overcast - overcast).clamp(-step, step);
+
+        precipitation.set_kind(current_weather);
+        if let Some(center) = entity::get_component(player, translation()) {
+            precipitation.update(center, dt);
+        }
+
         let to_game_time =
             entity::get_component(entity::synchronized_resources(), real_time_to_game_time())
                 .unwrap_or(0.0);
 
-        let tick = delta_time() as f64;
+        let Some(server_time) =
+            entity::get_component(entity::synchronized_resources(), time_of_day())
+        else {
+            return;
+        };
+
+        let tick = dt as f64;
         let hours = tick / 60.0 / 60.0;
-        let elapsed = hours * to_game_time;
-
-        let time = entity::mutate_component_with_default(
-            entity::synchronized_resources(),
-            time_of_day(),
-            12.0,
-            |time| {
-                *time += elapsed;
-                while *time > 24.0 {
-                    *time -= 24.0;
-                }
-            },
-        );
+        let time = clock.update(server_time, hours * to_game_time, tick);
 
         let theta = (time + 6.0) / 24.0 * TAU;
         let new_rotation = Quat::from_rotation_y(theta as f32);
@@ -59,12 +269,16 @@ fn main() {
         let diffuse = mix_day * diffuse_day + mix_night * diffuse_night;
         let ambient = mix_day * ambient_day + mix_night * ambient_night;
 
+        let diffuse = diffuse * (1.0 - overcast * OVERCAST_DIMMING);
+        let density = CLEAR_FOG_DENSITY + (OVERCAST_FOG_DENSITY - CLEAR_FOG_DENSITY) * overcast;
+
         entity::add_components(
             sun_entity,
             Entity::new()
                 .with(rotation(), new_rotation)
                 .with(light_diffuse(), diffuse)
-                .with(light_ambient(), ambient),
+                .with(light_ambient(), ambient)
+                .with(fog_density(), density),
         );
     });
 }
@@ -1,43 +1,315 @@
-use ambient_api::prelude::*;
+use std::{
+    collections::{HashMap, HashSet},
+    f32::consts::TAU,
+    sync::{Arc, Mutex},
+};
 
-use packages::this::components::*;
+use ambient_api::{core::player::components::user_id, prelude::*};
+
+use flowerpot_common::{ActorExt, SystemExt};
+use packages::{
+    map::components::chunk,
+    region_networking::{components::players_observing, messages::LoadPlayerRegion},
+    this::{components::*, messages::*},
+};
 
 mod shared;
 
+/// The number of days in each season.
+pub const DAYS_PER_SEASON: u32 = 7;
+
+/// The number of seasons in a year.
+pub const SEASON_NUM: u32 = 4;
+
+/// The default value of the real_time_to_game_time resource, so that a game
+/// day lasts 20 real minutes.
+pub const DEFAULT_REAL_TIME_TO_GAME_TIME: f64 = 72.0;
+
+/// The time in real seconds between each update to the time of day.
+pub const TIME_TICK_INTERVAL: f64 = 5.0;
+
+/// The number of chunks along each side of a weather cell. All of the chunks
+/// in a cell share the same weather.
+pub const WEATHER_CELL_SIZE: i32 = 4;
+
+/// The time in seconds between each update to the weather.
+pub const WEATHER_TICK_INTERVAL: f32 = 15.0;
+
+/// The chance each weather tick that the weather of a cell changes.
+pub const WEATHER_CHANGE_CHANCE: f32 = 0.1;
+
+/// The chance in each season that a change in the weather brings rain or snow.
+pub const PRECIPITATION_CHANCES: [f32; SEASON_NUM as usize] = [0.4, 0.25, 0.45, 0.35];
+
+/// The average temperature of each season.
+pub const SEASON_TEMPERATURES: [f32; SEASON_NUM as usize] = [12.0, 24.0, 11.0, -1.0];
+
+/// How much warmer than the season's average it gets in the afternoon, and
+/// how much colder it gets at night.
+pub const DAILY_TEMPERATURE_SWING: f32 = 6.0;
+
+/// The time of day at which it's warmest.
+pub const WARMEST_HOUR: f32 = 15.0;
+
+/// How much colder it is while it's raining or snowing.
+pub const PRECIPITATION_COOLING: f32 = 3.0;
+
+/// Precipitation falls as snow below this temperature.
+pub const SNOW_TEMPERATURE: f32 = 1.0;
+
+/// The most that a cell's temperature strays from the season's average.
+pub const MAX_TEMPERATURE_OFFSET: f32 = 3.0;
+
+pub fn get_time() -> f32 {
+    entity::get_component(entity::synchronized_resources(), time_of_day()).unwrap_or(12.0) as f32
+}
+
+pub fn get_season() -> u8 {
+    entity::get_component(entity::synchronized_resources(), season()).unwrap_or(0)
+}
+
+pub fn get_year() -> u32 {
+    entity::get_component(entity::synchronized_resources(), year()).unwrap_or(0)
+}
+
+/// Calculates the season and year of a day.
+pub fn get_calendar(today: u32) -> (u8, u32) {
+    let seasons = today / DAYS_PER_SEASON;
+    ((seasons % SEASON_NUM) as u8, seasons / SEASON_NUM)
+}
+
+/// Sets the season and year resources to match a day.
+fn set_calendar(today: u32) -> (u8, u32) {
+    let (new_season, new_year) = get_calendar(today);
+    entity::add_component(entity::synchronized_resources(), season(), new_season);
+    entity::add_component(entity::synchronized_resources(), year(), new_year);
+    (new_season, new_year)
+}
+
+/// Starts a new day, broadcasting the calendar events.
+fn advance_day() {
+    let last_season = get_season();
+    let resources = entity::synchronized_resources();
+    let today = entity::mutate_component_with_default(resources, day(), 0, |today| *today += 1);
+
+    OnNewDay::new(today).send_local_broadcast(true);
+
+    let (new_season, new_year) = set_calendar(today);
+    if new_season != last_season {
+        OnNewSeason::new(new_season, new_year).send_local_broadcast(true);
+    }
+}
+
+/// Calculates the clear-weather temperature of a season at a time of day.
+pub fn get_base_temperature(current_season: u8, time: f32) -> f32 {
+    let swing = ((time - WARMEST_HOUR) / 24.0 * TAU).cos() * DAILY_TEMPERATURE_SWING;
+    SEASON_TEMPERATURES[current_season as usize % SEASON_NUM as usize] + swing
+}
+
+/// The weather of a group of neighboring chunks.
+#[derive(Clone, Copy, Debug)]
+pub struct WeatherCell {
+    pub precipitating: bool,
+    pub temperature_offset: f32,
+}
+
+impl WeatherCell {
+    pub fn random(current_season: u8) -> Self {
+        let chance = PRECIPITATION_CHANCES[current_season as usize % SEASON_NUM as usize];
+        Self {
+            precipitating: random::<f32>() < chance,
+            temperature_offset: (random::<f32>() * 2.0 - 1.0) * MAX_TEMPERATURE_OFFSET,
+        }
+    }
+
+    /// Randomly changes the weather.
+    pub fn step(&mut self, current_season: u8) {
+        if random::<f32>() < WEATHER_CHANGE_CHANCE {
+            let chance = PRECIPITATION_CHANCES[current_season as usize % SEASON_NUM as usize];
+            self.precipitating = random::<f32>() < chance;
+        }
+
+        let drift = random::<f32>() - 0.5;
+        self.temperature_offset = (self.temperature_offset + drift)
+            .clamp(-MAX_TEMPERATURE_OFFSET, MAX_TEMPERATURE_OFFSET);
+    }
+
+    /// Returns the value of the weather component and the temperature.
+    pub fn get(&self, current_season: u8, time: f32) -> (u8, f32) {
+        let temp = get_base_temperature(current_season, time) + self.temperature_offset;
+        if !self.precipitating {
+            return (shared::CLEAR, temp);
+        }
+
+        let temp = temp - PRECIPITATION_COOLING;
+        if temp < SNOW_TEMPERATURE {
+            (shared::SNOW, temp)
+        } else {
+            (shared::RAIN, temp)
+        }
+    }
+}
+
+/// Tracks the weather of every loaded chunk.
+#[derive(Default)]
+pub struct WeatherMap {
+    pub cells: HashMap<IVec2, WeatherCell>,
+    pub chunks: HashMap<EntityId, IVec2>,
+
+    /// The weather and rounded temperature last written to each chunk and sent
+    /// to its observers.
+    pub sent: HashMap<EntityId, (u8, i32)>,
+}
+
+impl WeatherMap {
+    pub fn get_cell(position: IVec2) -> IVec2 {
+        position.div_euclid(IVec2::splat(WEATHER_CELL_SIZE))
+    }
+
+    pub fn add_chunk(&mut self, e: EntityId, position: IVec2) {
+        self.chunks.insert(e, position);
+        self.cells
+            .entry(Self::get_cell(position))
+            .or_insert_with(|| WeatherCell::random(get_season()));
+        self.update_chunk(e, position);
+    }
+
+    pub fn remove_chunk(&mut self, e: EntityId) {
+        self.chunks.remove(&e);
+        self.sent.remove(&e);
+    }
+
+    /// Changes the weather of every loaded cell and updates their chunks.
+    pub fn step(&mut self) {
+        let current_season = get_season();
+
+        // forget the weather of cells that are no longer loaded
+        let loaded: HashSet<IVec2> = self.chunks.values().copied().map(Self::get_cell).collect();
+        self.cells.retain(|cell, _| loaded.contains(cell));
+
+        for cell in self.cells.values_mut() {
+            cell.step(current_season);
+        }
+
+        let chunks: Vec<_> = self.chunks.iter().map(|(e, xy)| (*e, *xy)).collect();
+        for (e, position) in chunks {
+            self.update_chunk(e, position);
+        }
+    }
+
+    fn update_chunk(&mut self, e: EntityId, position: IVec2) {
+        let Some(cell) = self.cells.get(&Self::get_cell(position)) else {
+            return;
+        };
+
+        let (state, temp) = cell.get(get_season(), get_time());
+
+        // only update and network changes that players and crops would notice
+        let key = (state, temp.round() as i32);
+        if self.sent.get(&e) == Some(&key) {
+            return;
+        }
+
+        self.sent.insert(e, key);
+        entity::add_component(e, weather(), state);
+        entity::add_component(e, temperature(), temp);
+
+        for player in entity::get_component(e, players_observing()).unwrap_or_default() {
+            if let Some(uid) = entity::get_component(player, user_id()) {
+                UpdateChunkWeather::new(position, state, temp).send_client_targeted_reliable(uid);
+            }
+        }
+    }
+}
+
 #[main]
 fn main() {
-    entity::add_component(
-        entity::synchronized_resources(),
-        real_time_to_game_time(),
-        // 1000.0,
-        0.0,
-    );
+    if !entity::has_component(entity::synchronized_resources(), real_time_to_game_time()) {
+        entity::add_component(
+            entity::synchronized_resources(),
+            real_time_to_game_time(),
+            DEFAULT_REAL_TIME_TO_GAME_TIME,
+        );
+    }
 
-    entity::add_component(entity::synchronized_resources(), time_of_day(), 9.0);
+    // the time of day and calendar may have already been restored from a save
+    if !entity::has_component(entity::synchronized_resources(), time_of_day()) {
+        entity::add_component(entity::synchronized_resources(), time_of_day(), 9.0);
+    }
+
+    let today = entity::get_component(entity::synchronized_resources(), day()).unwrap_or(0);
+    entity::add_component(entity::synchronized_resources(), day(), today);
+    set_calendar(today);
+
+    let map = Arc::new(Mutex::new(WeatherMap::default()));
+
+    map.on_event(spawn_query(chunk()), WeatherMap::add_chunk);
+
+    map.on_event(despawn_query(chunk()), move |map, e, _position| {
+        map.remove_chunk(e);
+    });
 
+    // send the current weather to players that start observing a chunk
+    map.on_local_message(move |_map, _, data: LoadPlayerRegion| {
+        let Some(position) = entity::get_component(data.region, chunk()) else {
+            return;
+        };
+
+        let Some(state) = entity::get_component(data.region, weather()) else {
+            return;
+        };
+
+        let temp = entity::get_component(data.region, temperature()).unwrap_or_default();
+        UpdateChunkWeather::new(position, state, temp)
+            .send_client_targeted_reliable(data.player_uid);
+    });
+
+    run_async({
+        let map = map.clone();
+        async move {
+            loop {
+                sleep(WEATHER_TICK_INTERVAL).await;
+                map.lock().unwrap().step();
+            }
+        }
+    });
+
+    // the server is the only side that advances the clock
     run_async(async move {
         loop {
-            let tick = 5.0f64;
-            sleep(tick as f32).await;
+            sleep(TIME_TICK_INTERVAL as f32).await;
+
+            // the day may have been restored from a save after startup
+            let today = entity::get_component(entity::synchronized_resources(), day());
+            let today = today.unwrap_or(0);
+            if get_calendar(today) != (get_season(), get_year()) {
+                set_calendar(today);
+            }
 
             let to_game_time =
                 entity::get_component(entity::synchronized_resources(), real_time_to_game_time())
-                    .unwrap_or(0.0);
+                    .unwrap_or(DEFAULT_REAL_TIME_TO_GAME_TIME);
 
-            let hours = tick / 60.0 / 60.0;
+            let hours = TIME_TICK_INTERVAL / 60.0 / 60.0;
             let elapsed = hours * to_game_time;
 
+            let mut days_passed = 0;
             entity::mutate_component_with_default(
                 entity::synchronized_resources(),
                 time_of_day(),
                 12.0,
                 |time| {
                     *time += elapsed;
-                    while *time > 24.0 {
+                    while *time >= 24.0 {
                         *time -= 24.0;
+                        days_passed += 1;
                     }
                 },
             );
+
+            for _ in 0..days_passed {
+                advance_day();
+            }
         }
     });
 }
@@ -0,0 +1,5 @@
+//! Values of the weather component.
+
+pub const CLEAR: u8 = 0;
+pub const RAIN: u8 = 1;
+pub const SNOW: u8 = 2;
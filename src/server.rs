@@ -1,14 +1,21 @@
 use std::sync::atomic::AtomicBool;
 
-use ambient_api::{once_cell::sync::OnceCell, prelude::*};
+use ambient_api::{core::player::components::is_player, once_cell::sync::OnceCell, prelude::*};
 
-use flowerpot_common::CHUNK_SIZE;
+use flowerpot_common::{spawn_instance, CHUNK_SIZE};
 use packages::{
-    crops::components::{coords, is_medium_crop, medium_crop_occupant, on_tile},
+    content::messages::LoadContent,
+    crops::components::{coords, medium_crop_occupant, on_tile},
     game::components::*,
-    map::components::{chunk, chunk_tile_refs},
+    items::components::{
+        class, durability, held_ref, is_item, is_tool, item_tags, max_durability, quantity,
+    },
+    map::components::{chunk, chunk_tile_refs, speed_multiplier},
     nameplate::components::name,
-    things::components::{class_ref, is_class, model_prefab_url as prefab},
+    player::components::{left_hand_ref, right_hand_ref},
+    save::components::{is_new_player, is_new_world},
+    storage::components::{chest_size, is_chest},
+    things::components::{class_id, class_ref, is_class, model_prefab_url as prefab},
     this::assets::url,
 };
 
@@ -39,6 +46,22 @@ impl PrototypeEntity {
     }
 }
 
+/// Derives a stable class ID for a prototype from its path in this crate.
+pub fn prototype_id(module_path: &str, name: &str) -> String {
+    // strip the crate name so that IDs don't depend on the binary's name
+    let module_path = module_path
+        .split_once("::")
+        .map(|(_krate, path)| path)
+        .unwrap_or(module_path);
+
+    format!("{}::{}", module_path, name)
+}
+
+/// Converts a list of tags into owned strings for use as a component value.
+pub fn tags(tags: &[&str]) -> Vec<String> {
+    tags.iter().map(|tag| tag.to_string()).collect()
+}
+
 macro_rules! expand_props {
     ($e:expr, $component:ident: $value:expr $(, $component_tail:ident: $value_tail:expr)* $(,)?) => {
         expand_props!(Entity::with($e, $component(), $value.into()) $(, $component_tail: $value_tail)*)
@@ -52,17 +75,23 @@ macro_rules! def_entity {
     }
 }
 
+/// Defines a class prototype from a list of components. A base entity may be
+/// given first to share components between related prototypes.
 macro_rules! def_prototype {
-    ($item_name:ident $(, $component:ident: $value:expr)* $(,)?) => {
+    ($item_name:ident, base: $base:expr $(, $component:ident: $value:expr)* $(,)?) => {
         ::lazy_static::lazy_static! {
             pub static ref $item_name: PrototypeEntity = PrototypeEntity::new(move |e| {
                 entity::add_components(e,
-                    def_entity!($($component: $value),*)
+                    expand_props!($base $(, $component: $value)*)
                     .with(is_class(), ())
+                    .with(class_id(), prototype_id(module_path!(), stringify!($item_name)))
                 );
             });
         }
-    }
+    };
+    ($item_name:ident $(, $component:ident: $value:expr)* $(,)?) => {
+        def_prototype!($item_name, base: Entity::new() $(, $component: $value)*);
+    };
 }
 
 pub mod crops {
@@ -72,6 +101,52 @@ pub mod crops {
         next_growth_age as next_age, next_growth_stage as next_stage, *,
     };
 
+    /// The age that each unripe growth stage needs to reach to grow.
+    pub const GROWTH_AGE: u16 = 300;
+
+    /// How long a ripe crop lasts before it withers away if not picked.
+    pub const WITHER_AGE: u16 = 1800;
+
+    /// The age between each attempt by a ripe crop to seed its neighbors.
+    pub const SEEDING_INTERVAL: u16 = 600;
+
+    /// The speed multiplier for players walking through tall crops.
+    pub const TALL_CROP_SPEED_MULTIPLIER: f32 = 0.6;
+
+    /// The age that each growth stage of a tree needs to reach to grow.
+    pub const TREE_GROWTH_AGE: u16 = 600;
+
+    /// How long a tree holds its fruit before dropping it if not picked.
+    pub const TREE_FRUIT_AGE: u16 = 1200;
+
+    /// The area covered by every growth stage of a tree, so that trees never
+    /// need more room as they grow.
+    pub const TREE_FOOTPRINT: UVec2 = UVec2::new(2, 2);
+
+    /// The number of model variants of each tree growth stage.
+    pub const TREE_VARIANT_NUM: u32 = 4;
+
+    pub const SPRING: u8 = 0;
+    pub const SUMMER: u8 = 1;
+    pub const AUTUMN: u8 = 2;
+    pub const WINTER: u8 = 3;
+
+    /// The temperatures that fruit trees grow at full speed in.
+    pub const TREE_COMFORTABLE_TEMPERATURE: Vec2 = Vec2::new(8.0, 28.0);
+
+    /// Trees are hardy enough to survive any weather.
+    pub const TREE_SURVIVABLE_TEMPERATURE: Vec2 = Vec2::new(-40.0, 50.0);
+
+    /// Lists the model variants of a tree's growth stage.
+    fn tree_variants(category: &str, stage: &str) -> Vec<String> {
+        (1..=TREE_VARIANT_NUM)
+            .map(|variant| {
+                let path = format!("crops/large/{category}/{category}_{stage}_{variant}.fbx");
+                url(&path)
+            })
+            .collect()
+    }
+
     lazy_static::lazy_static! {
         pub static ref SHOWCASE: Vec<Vec<EntityId>> = vec![
             // beans
@@ -83,7 +158,6 @@ pub mod crops {
                 beans::STAGE_4.get(),
                 beans::STAGE_5.get(),
                 beans::STAGE_6.get(),
-
             ],
 
             // carrots
@@ -178,585 +252,1575 @@ pub mod crops {
                 wheat::STAGE_7.get(),
             ],
         ];
+
+        pub static ref TREE_SHOWCASE: Vec<Vec<EntityId>> = vec![
+            // apple
+            vec![
+                apple::STAGE_0.get(),
+                apple::STAGE_1.get(),
+                apple::STAGE_2.get(),
+                apple::STAGE_3.get(),
+                apple::STAGE_4.get(),
+                apple::STAGE_5.get(),
+                apple::STAGE_6.get(),
+            ],
+
+            // blue spruce
+            vec![
+                blue_spruce::STAGE_0.get(),
+                blue_spruce::STAGE_1.get(),
+                blue_spruce::STAGE_2.get(),
+                blue_spruce::STAGE_3.get(),
+                blue_spruce::STAGE_4.get(),
+                blue_spruce::STAGE_5.get(),
+            ],
+
+            // cherry
+            vec![
+                cherry::STAGE_0.get(),
+                cherry::STAGE_1.get(),
+                cherry::STAGE_2.get(),
+                cherry::STAGE_3.get(),
+                cherry::STAGE_4.get(),
+                cherry::STAGE_5.get(),
+                cherry::STAGE_6.get(),
+            ],
+
+            // peach
+            vec![
+                peach::STAGE_0.get(),
+                peach::STAGE_1.get(),
+                peach::STAGE_2.get(),
+                peach::STAGE_3.get(),
+                peach::STAGE_4.get(),
+                peach::STAGE_5.get(),
+            ],
+        ];
     }
 
     pub mod beans {
         use super::*;
 
+        const SEASONS: &[u8] = &[SPRING, SUMMER];
+        const COMFORTABLE_TEMPERATURE: Vec2 = Vec2::new(14.0, 30.0);
+        const SURVIVABLE_TEMPERATURE: Vec2 = Vec2::new(-2.0, 40.0);
+
+        /// The components shared by every growth stage.
+        fn base_stage() -> Entity {
+            def_entity!(
+                is_medium_crop: (),
+                crop_tags: tags(&["beans", "legume"]),
+                allowed_seasons: SEASONS.to_vec(),
+                comfortable_temperature: COMFORTABLE_TEMPERATURE,
+                survivable_temperature: SURVIVABLE_TEMPERATURE,
+                inhibitor_tags: tags(&["weed"]),
+            )
+        }
+
         def_prototype!(
             STAGE_0,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_1.get(),
             prefab: url("crops/medium/Beans/Beans_0.fbx"),
-            name: "Beans_0"
+            name: "Beans_0",
         );
 
         def_prototype!(
             STAGE_1,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_2.get(),
             prefab: url("crops/medium/Beans/Beans_1.fbx"),
-            name: "Beans_1"
+            name: "Beans_1",
         );
 
         def_prototype!(
             STAGE_2,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_3.get(),
             prefab: url("crops/medium/Beans/Beans_2.fbx"),
-            name: "Beans_2"
+            name: "Beans_2",
         );
 
         def_prototype!(
             STAGE_3,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_4.get(),
             prefab: url("crops/medium/Beans/Beans_3.fbx"),
-            name: "Beans_3"
+            name: "Beans_3",
         );
 
         def_prototype!(
             STAGE_4,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_5.get(),
             prefab: url("crops/medium/Beans/Beans_4.fbx"),
-            name: "Beans_4"
+            name: "Beans_4",
         );
 
         def_prototype!(
             STAGE_5,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_6.get(),
             prefab: url("crops/medium/Beans/Beans_5.fbx"),
-            name: "Beans_5"
+            name: "Beans_5",
         );
 
         def_prototype!(
             STAGE_6,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: WITHER_AGE,
+            next_stage: EntityId::null(),
+            seed: STAGE_0.get(),
+            seeding_interval: SEEDING_INTERVAL,
+            pick_up_item_class: items::beans::POD.get(),
+            pick_up_next_stage: STAGE_4.get(),
             prefab: url("crops/medium/Beans/Beans_6.fbx"),
-            name: "Beans_6"
+            name: "Beans_6",
         );
     }
 
     pub mod carrots {
         use super::*;
 
+        const SEASONS: &[u8] = &[SPRING, SUMMER, AUTUMN];
+        const COMFORTABLE_TEMPERATURE: Vec2 = Vec2::new(8.0, 24.0);
+        const SURVIVABLE_TEMPERATURE: Vec2 = Vec2::new(-8.0, 35.0);
+
+        /// The components shared by every growth stage.
+        fn base_stage() -> Entity {
+            def_entity!(
+                is_medium_crop: (),
+                crop_tags: tags(&["carrots"]),
+                allowed_seasons: SEASONS.to_vec(),
+                comfortable_temperature: COMFORTABLE_TEMPERATURE,
+                survivable_temperature: SURVIVABLE_TEMPERATURE,
+                inhibitor_tags: tags(&["weed"]),
+            )
+        }
+
         def_prototype!(
             STAGE_0,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_1.get(),
             prefab: url("crops/medium/Carrots/Carrot_0.fbx"),
-            name: "Carrot_0"
+            name: "Carrot_0",
         );
 
         def_prototype!(
             STAGE_1,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_2.get(),
             prefab: url("crops/medium/Carrots/Carrot_1.fbx"),
-            name: "Carrot_1"
+            name: "Carrot_1",
         );
 
         def_prototype!(
             STAGE_2,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_3.get(),
             prefab: url("crops/medium/Carrots/Carrot_2.fbx"),
-            name: "Carrot_2"
+            name: "Carrot_2",
         );
 
         def_prototype!(
             STAGE_3,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_4.get(),
             prefab: url("crops/medium/Carrots/Carrot_3.fbx"),
-            name: "Carrot_3"
+            name: "Carrot_3",
         );
 
         def_prototype!(
             STAGE_4,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_5.get(),
             prefab: url("crops/medium/Carrots/Carrot_4.fbx"),
-            name: "Carrot_4"
+            name: "Carrot_4",
         );
 
         def_prototype!(
             STAGE_5,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: WITHER_AGE,
+            next_stage: EntityId::null(),
+            seed: STAGE_0.get(),
+            seeding_interval: SEEDING_INTERVAL,
+            pick_up_item_class: items::carrots::CARROT.get(),
             prefab: url("crops/medium/Carrots/Carrot_5.fbx"),
-            name: "Carrot_5"
+            name: "Carrot_5",
         );
     }
 
     pub mod corn {
         use super::*;
 
+        const SEASONS: &[u8] = &[SPRING, SUMMER];
+        const COMFORTABLE_TEMPERATURE: Vec2 = Vec2::new(16.0, 32.0);
+        const SURVIVABLE_TEMPERATURE: Vec2 = Vec2::new(-2.0, 42.0);
+
+        /// The components shared by every growth stage.
+        fn base_stage() -> Entity {
+            def_entity!(
+                is_medium_crop: (),
+                crop_tags: tags(&["corn"]),
+                allowed_seasons: SEASONS.to_vec(),
+                comfortable_temperature: COMFORTABLE_TEMPERATURE,
+                survivable_temperature: SURVIVABLE_TEMPERATURE,
+                inhibitor_tags: tags(&["weed"]),
+                companion_tags: tags(&["legume"]),
+            )
+        }
+
         def_prototype!(
             STAGE_0,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_1.get(),
             prefab: url("crops/medium/Corn/Corn_0.fbx"),
-            name: "Corn_0"
+            name: "Corn_0",
         );
 
         def_prototype!(
             STAGE_1,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_2.get(),
             prefab: url("crops/medium/Corn/Corn_1.fbx"),
-            name: "Corn_1"
+            name: "Corn_1",
         );
 
         def_prototype!(
             STAGE_2,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_3.get(),
             prefab: url("crops/medium/Corn/Corn_2.fbx"),
-            name: "Corn_2"
+            name: "Corn_2",
         );
 
         def_prototype!(
             STAGE_3,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_4.get(),
             prefab: url("crops/medium/Corn/Corn_3.fbx"),
-            name: "Corn_3"
+            name: "Corn_3",
         );
 
         def_prototype!(
             STAGE_4,
-            is_medium_crop: (),
+            base: base_stage(),
+            speed_multiplier: TALL_CROP_SPEED_MULTIPLIER,
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_5.get(),
             prefab: url("crops/medium/Corn/Corn_4.fbx"),
-            name: "Corn_4"
+            name: "Corn_4",
         );
 
         def_prototype!(
             STAGE_5,
-            is_medium_crop: (),
+            base: base_stage(),
+            speed_multiplier: TALL_CROP_SPEED_MULTIPLIER,
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_6.get(),
+            pollination_tag: "corn",
+            pollination_threshold: 2_u8,
             prefab: url("crops/medium/Corn/Corn_5.fbx"),
-            name: "Corn_5"
+            name: "Corn_5",
         );
 
         def_prototype!(
             STAGE_6,
-            is_medium_crop: (),
+            base: base_stage(),
+            speed_multiplier: TALL_CROP_SPEED_MULTIPLIER,
+            next_age: WITHER_AGE,
+            next_stage: EntityId::null(),
+            seed: STAGE_0.get(),
+            seeding_interval: SEEDING_INTERVAL,
+            pick_up_item_class: items::corn::EAR.get(),
+            pick_up_next_stage: STAGE_7.get(),
             prefab: url("crops/medium/Corn/Corn_6.fbx"),
             name: "Corn_6",
-            pick_up_item_class: items::debug::YELLOW.get(),
-            pick_up_next_stage: STAGE_7.get(),
         );
 
         def_prototype!(
             STAGE_7,
-            is_medium_crop: (),
+            base: base_stage(),
+            speed_multiplier: TALL_CROP_SPEED_MULTIPLIER,
+            next_age: WITHER_AGE,
+            next_stage: EntityId::null(),
             prefab: url("crops/medium/Corn/Corn_7.fbx"),
-            name: "Corn_7"
-        );
-
-        def_prototype!(
-            STAGE_8,
-            is_medium_crop: (),
-            prefab: url("crops/medium/Corn/Corn_8.fbx"),
-            name: "Corn_8"
+            name: "Corn_7",
         );
     }
 
     pub mod garlic {
         use super::*;
 
+        const SEASONS: &[u8] = &[AUTUMN, WINTER, SPRING];
+        const COMFORTABLE_TEMPERATURE: Vec2 = Vec2::new(0.0, 20.0);
+        const SURVIVABLE_TEMPERATURE: Vec2 = Vec2::new(-20.0, 32.0);
+
+        /// The components shared by every growth stage.
+        fn base_stage() -> Entity {
+            def_entity!(
+                is_medium_crop: (),
+                crop_tags: tags(&["garlic"]),
+                allowed_seasons: SEASONS.to_vec(),
+                comfortable_temperature: COMFORTABLE_TEMPERATURE,
+                survivable_temperature: SURVIVABLE_TEMPERATURE,
+                inhibitor_tags: tags(&["weed"]),
+            )
+        }
+
         def_prototype!(
             STAGE_0,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_1.get(),
             prefab: url("crops/medium/Garlic/Garlic_0.fbx"),
-            name: "Garlic_0"
+            name: "Garlic_0",
         );
 
         def_prototype!(
             STAGE_1,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_2.get(),
             prefab: url("crops/medium/Garlic/Garlic_1.fbx"),
-            name: "Garlic_1"
+            name: "Garlic_1",
         );
 
         def_prototype!(
             STAGE_2,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_3.get(),
             prefab: url("crops/medium/Garlic/Garlic_2.fbx"),
-            name: "Garlic_2"
+            name: "Garlic_2",
         );
 
         def_prototype!(
             STAGE_3,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_4.get(),
             prefab: url("crops/medium/Garlic/Garlic_3.fbx"),
-            name: "Garlic_3"
+            name: "Garlic_3",
         );
 
         def_prototype!(
             STAGE_4,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_5.get(),
             prefab: url("crops/medium/Garlic/Garlic_4.fbx"),
-            name: "Garlic_4"
+            name: "Garlic_4",
         );
 
         def_prototype!(
             STAGE_5,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_6.get(),
             prefab: url("crops/medium/Garlic/Garlic_5.fbx"),
-            name: "Garlic_5"
+            name: "Garlic_5",
         );
 
         def_prototype!(
             STAGE_6,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_7.get(),
             prefab: url("crops/medium/Garlic/Garlic_6.fbx"),
-            name: "Garlic_6"
+            name: "Garlic_6",
         );
 
         def_prototype!(
             STAGE_7,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: WITHER_AGE,
+            next_stage: EntityId::null(),
+            seed: STAGE_0.get(),
+            seeding_interval: SEEDING_INTERVAL,
+            pick_up_item_class: items::garlic::BULB.get(),
             prefab: url("crops/medium/Garlic/Garlic_7.fbx"),
-            name: "Garlic_7"
+            name: "Garlic_7",
         );
     }
 
     pub mod peppers {
         use super::*;
 
+        const SEASONS: &[u8] = &[SPRING, SUMMER];
+        const COMFORTABLE_TEMPERATURE: Vec2 = Vec2::new(18.0, 32.0);
+        const SURVIVABLE_TEMPERATURE: Vec2 = Vec2::new(0.0, 40.0);
+
+        /// The components shared by every growth stage.
+        fn base_stage() -> Entity {
+            def_entity!(
+                is_medium_crop: (),
+                crop_tags: tags(&["peppers"]),
+                allowed_seasons: SEASONS.to_vec(),
+                comfortable_temperature: COMFORTABLE_TEMPERATURE,
+                survivable_temperature: SURVIVABLE_TEMPERATURE,
+                inhibitor_tags: tags(&["weed"]),
+                companion_tags: tags(&["flower"]),
+            )
+        }
+
         def_prototype!(
             STAGE_0,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_1.get(),
             prefab: url("crops/medium/Peppers/Peppers_0.fbx"),
-            name: "Peppers_0"
+            name: "Peppers_0",
         );
 
         def_prototype!(
             STAGE_1,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_2.get(),
             prefab: url("crops/medium/Peppers/Peppers_1.fbx"),
-            name: "Peppers_1"
+            name: "Peppers_1",
         );
 
         def_prototype!(
             STAGE_2,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_3.get(),
             prefab: url("crops/medium/Peppers/Peppers_2.fbx"),
-            name: "Peppers_2"
+            name: "Peppers_2",
         );
 
         def_prototype!(
             STAGE_3,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_4.get(),
             prefab: url("crops/medium/Peppers/Peppers_3.fbx"),
-            name: "Peppers_3"
+            name: "Peppers_3",
         );
 
         def_prototype!(
             STAGE_4,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_5.get(),
             prefab: url("crops/medium/Peppers/Peppers_4.fbx"),
-            name: "Peppers_4"
+            name: "Peppers_4",
         );
 
         def_prototype!(
             STAGE_5,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_6.get(),
             prefab: url("crops/medium/Peppers/Peppers_5.fbx"),
-            name: "Peppers_5"
+            name: "Peppers_5",
         );
 
         def_prototype!(
             STAGE_6,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_7.get(),
             prefab: url("crops/medium/Peppers/Peppers_6.fbx"),
-            name: "Peppers_6"
+            name: "Peppers_6",
         );
 
         def_prototype!(
             STAGE_7,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_8.get(),
             prefab: url("crops/medium/Peppers/Peppers_7.fbx"),
-            name: "Peppers_7"
+            name: "Peppers_7",
         );
 
         def_prototype!(
             STAGE_8,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: WITHER_AGE,
+            next_stage: EntityId::null(),
+            seed: STAGE_0.get(),
+            seeding_interval: SEEDING_INTERVAL,
+            pick_up_item_class: items::peppers::PEPPER.get(),
+            pick_up_next_stage: STAGE_6.get(),
             prefab: url("crops/medium/Peppers/Peppers_8.fbx"),
-            name: "Peppers_8"
+            name: "Peppers_8",
         );
     }
 
     pub mod potatos {
         use super::*;
 
+        const SEASONS: &[u8] = &[SPRING, SUMMER, AUTUMN];
+        const COMFORTABLE_TEMPERATURE: Vec2 = Vec2::new(10.0, 24.0);
+        const SURVIVABLE_TEMPERATURE: Vec2 = Vec2::new(-4.0, 35.0);
+
+        /// The components shared by every growth stage.
+        fn base_stage() -> Entity {
+            def_entity!(
+                is_medium_crop: (),
+                crop_tags: tags(&["potatos"]),
+                allowed_seasons: SEASONS.to_vec(),
+                comfortable_temperature: COMFORTABLE_TEMPERATURE,
+                survivable_temperature: SURVIVABLE_TEMPERATURE,
+                inhibitor_tags: tags(&["weed"]),
+            )
+        }
+
         def_prototype!(
             STAGE_0,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_1.get(),
             prefab: url("crops/medium/Potatos/Potato_0.fbx"),
-            name: "Potato_0"
+            name: "Potato_0",
         );
 
         def_prototype!(
             STAGE_1,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_2.get(),
             prefab: url("crops/medium/Potatos/Potato_1.fbx"),
-            name: "Potato_1"
+            name: "Potato_1",
         );
 
         def_prototype!(
             STAGE_2,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_3.get(),
             prefab: url("crops/medium/Potatos/Potato_2.fbx"),
-            name: "Potato_2"
+            name: "Potato_2",
         );
 
         def_prototype!(
             STAGE_3,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_4.get(),
             prefab: url("crops/medium/Potatos/Potato_3.fbx"),
-            name: "Potato_3"
+            name: "Potato_3",
         );
 
         def_prototype!(
             STAGE_4,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_5.get(),
             prefab: url("crops/medium/Potatos/Potato_4.fbx"),
-            name: "Potato_4"
+            name: "Potato_4",
         );
 
         def_prototype!(
             STAGE_5,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: WITHER_AGE,
+            next_stage: EntityId::null(),
+            seed: STAGE_0.get(),
+            seeding_interval: SEEDING_INTERVAL,
+            pick_up_item_class: items::potatos::POTATO.get(),
             prefab: url("crops/medium/Potatos/Potato_5.fbx"),
-            name: "Potato_5"
+            name: "Potato_5",
         );
     }
 
     pub mod sugarcane {
         use super::*;
 
+        const SEASONS: &[u8] = &[SUMMER];
+        const COMFORTABLE_TEMPERATURE: Vec2 = Vec2::new(20.0, 35.0);
+        const SURVIVABLE_TEMPERATURE: Vec2 = Vec2::new(2.0, 42.0);
+
+        /// The components shared by every growth stage.
+        fn base_stage() -> Entity {
+            def_entity!(
+                is_medium_crop: (),
+                crop_tags: tags(&["sugarcane"]),
+                allowed_seasons: SEASONS.to_vec(),
+                comfortable_temperature: COMFORTABLE_TEMPERATURE,
+                survivable_temperature: SURVIVABLE_TEMPERATURE,
+                inhibitor_tags: tags(&["weed"]),
+            )
+        }
+
         def_prototype!(
             STAGE_0,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_1.get(),
             prefab: url("crops/medium/Sugarcane/Sugarcane_0.fbx"),
-            name: "Sugarcane_0"
+            name: "Sugarcane_0",
         );
 
         def_prototype!(
             STAGE_1,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_2.get(),
             prefab: url("crops/medium/Sugarcane/Sugarcane_1.fbx"),
-            name: "Sugarcane_1"
+            name: "Sugarcane_1",
         );
 
         def_prototype!(
             STAGE_2,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_3.get(),
             prefab: url("crops/medium/Sugarcane/Sugarcane_2.fbx"),
-            name: "Sugarcane_2"
+            name: "Sugarcane_2",
         );
 
         def_prototype!(
             STAGE_3,
-            is_medium_crop: (),
+            base: base_stage(),
+            speed_multiplier: TALL_CROP_SPEED_MULTIPLIER,
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_4.get(),
             prefab: url("crops/medium/Sugarcane/Sugarcane_3.fbx"),
-            name: "Sugarcane_3"
+            name: "Sugarcane_3",
         );
 
         def_prototype!(
             STAGE_4,
-            is_medium_crop: (),
+            base: base_stage(),
+            speed_multiplier: TALL_CROP_SPEED_MULTIPLIER,
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_5.get(),
             prefab: url("crops/medium/Sugarcane/Sugarcane_4.fbx"),
-            name: "Sugarcane_4"
+            name: "Sugarcane_4",
         );
 
         def_prototype!(
             STAGE_5,
-            is_medium_crop: (),
+            base: base_stage(),
+            speed_multiplier: TALL_CROP_SPEED_MULTIPLIER,
+            next_age: WITHER_AGE,
+            next_stage: EntityId::null(),
+            seed: STAGE_0.get(),
+            seeding_interval: SEEDING_INTERVAL,
+            pick_up_item_class: items::sugarcane::STALK.get(),
+            pick_up_next_stage: STAGE_2.get(),
             prefab: url("crops/medium/Sugarcane/Sugarcane_5.fbx"),
-            name: "Sugarcane_5"
+            name: "Sugarcane_5",
         );
     }
 
     pub mod tomatoes {
         use super::*;
 
+        const SEASONS: &[u8] = &[SPRING, SUMMER];
+        const COMFORTABLE_TEMPERATURE: Vec2 = Vec2::new(18.0, 30.0);
+        const SURVIVABLE_TEMPERATURE: Vec2 = Vec2::new(0.0, 40.0);
+
+        /// The components shared by every growth stage.
+        fn base_stage() -> Entity {
+            def_entity!(
+                is_medium_crop: (),
+                crop_tags: tags(&["tomatoes"]),
+                allowed_seasons: SEASONS.to_vec(),
+                comfortable_temperature: COMFORTABLE_TEMPERATURE,
+                survivable_temperature: SURVIVABLE_TEMPERATURE,
+                inhibitor_tags: tags(&["weed"]),
+                companion_tags: tags(&["flower"]),
+            )
+        }
+
         def_prototype!(
             STAGE_0,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_1.get(),
             prefab: url("crops/medium/Tomatos/Tomatos_0.fbx"),
-            name: "Tomatos_0"
+            name: "Tomatos_0",
         );
 
         def_prototype!(
             STAGE_1,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_2.get(),
             prefab: url("crops/medium/Tomatos/Tomatos_1.fbx"),
-            name: "Tomatos_1"
+            name: "Tomatos_1",
         );
 
         def_prototype!(
             STAGE_2,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_3.get(),
             prefab: url("crops/medium/Tomatos/Tomatos_2.fbx"),
-            name: "Tomatos_2"
+            name: "Tomatos_2",
         );
 
         def_prototype!(
             STAGE_3,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_4.get(),
             prefab: url("crops/medium/Tomatos/Tomatos_3.fbx"),
-            name: "Tomatos_3"
+            name: "Tomatos_3",
         );
 
         def_prototype!(
             STAGE_4,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_5.get(),
             prefab: url("crops/medium/Tomatos/Tomatos_4.fbx"),
-            name: "Tomatos_4"
+            name: "Tomatos_4",
         );
 
         def_prototype!(
             STAGE_5,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_6.get(),
             prefab: url("crops/medium/Tomatos/Tomatos_5.fbx"),
-            name: "Tomatos_5"
+            name: "Tomatos_5",
         );
 
         def_prototype!(
             STAGE_6,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_7.get(),
             prefab: url("crops/medium/Tomatos/Tomatos_6.fbx"),
-            name: "Tomatos_6"
+            name: "Tomatos_6",
         );
 
         def_prototype!(
             STAGE_7,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_8.get(),
             prefab: url("crops/medium/Tomatos/Tomatos_7.fbx"),
-            name: "Tomatos_7"
+            name: "Tomatos_7",
         );
 
         def_prototype!(
             STAGE_8,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: WITHER_AGE,
+            next_stage: EntityId::null(),
+            seed: STAGE_0.get(),
+            seeding_interval: SEEDING_INTERVAL,
+            pick_up_item_class: items::tomatoes::FRUIT.get(),
+            pick_up_next_stage: STAGE_6.get(),
             prefab: url("crops/medium/Tomatos/Tomatos_8.fbx"),
-            name: "Tomatos_8"
+            name: "Tomatos_8",
         );
     }
 
     pub mod wheat {
         use super::*;
 
+        const SEASONS: &[u8] = &[AUTUMN, WINTER, SPRING];
+        const COMFORTABLE_TEMPERATURE: Vec2 = Vec2::new(4.0, 24.0);
+        const SURVIVABLE_TEMPERATURE: Vec2 = Vec2::new(-20.0, 35.0);
+
+        /// The components shared by every growth stage.
+        fn base_stage() -> Entity {
+            def_entity!(
+                is_medium_crop: (),
+                crop_tags: tags(&["wheat"]),
+                allowed_seasons: SEASONS.to_vec(),
+                comfortable_temperature: COMFORTABLE_TEMPERATURE,
+                survivable_temperature: SURVIVABLE_TEMPERATURE,
+                inhibitor_tags: tags(&["weed"]),
+            )
+        }
+
         def_prototype!(
             STAGE_0,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_1.get(),
             prefab: url("crops/medium/Wheat/Wheat_0.fbx"),
-            name: "Wheat_0"
+            name: "Wheat_0",
         );
 
         def_prototype!(
             STAGE_1,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_2.get(),
             prefab: url("crops/medium/Wheat/Wheat_1.fbx"),
-            name: "Wheat_1"
+            name: "Wheat_1",
         );
 
         def_prototype!(
             STAGE_2,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_3.get(),
             prefab: url("crops/medium/Wheat/Wheat_2.fbx"),
-            name: "Wheat_2"
+            name: "Wheat_2",
         );
 
         def_prototype!(
             STAGE_3,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_4.get(),
             prefab: url("crops/medium/Wheat/Wheat_3.fbx"),
-            name: "Wheat_3"
+            name: "Wheat_3",
         );
 
         def_prototype!(
             STAGE_4,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_5.get(),
             prefab: url("crops/medium/Wheat/Wheat_4.fbx"),
-            name: "Wheat_4"
+            name: "Wheat_4",
         );
 
         def_prototype!(
             STAGE_5,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_6.get(),
             prefab: url("crops/medium/Wheat/Wheat_5.fbx"),
-            name: "Wheat_5"
+            name: "Wheat_5",
         );
 
         def_prototype!(
             STAGE_6,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_7.get(),
             prefab: url("crops/medium/Wheat/Wheat_6.fbx"),
-            name: "Wheat_6"
+            name: "Wheat_6",
         );
 
         def_prototype!(
             STAGE_7,
-            is_medium_crop: (),
+            base: base_stage(),
+            next_age: WITHER_AGE,
+            next_stage: EntityId::null(),
+            seed: STAGE_0.get(),
+            seeding_interval: SEEDING_INTERVAL,
+            pick_up_item_class: items::wheat::GRAINS.get(),
             prefab: url("crops/medium/Wheat/Wheat_7.fbx"),
-            name: "Wheat_7"
+            name: "Wheat_7",
+        );
+    }
+
+    pub mod apple {
+        use super::*;
+
+        /// The components shared by every growth stage.
+        fn base_stage() -> Entity {
+            def_entity!(
+                is_large_crop: (),
+                crop_tags: tags(&["apple", "tree"]),
+                comfortable_temperature: TREE_COMFORTABLE_TEMPERATURE,
+                survivable_temperature: TREE_SURVIVABLE_TEMPERATURE,
+                large_crop_footprint: TREE_FOOTPRINT,
+            )
+        }
+
+        def_prototype!(
+            STAGE_0,
+            base: base_stage(),
+            next_age: TREE_GROWTH_AGE,
+            next_stage: STAGE_1.get(),
+            model_variants: tree_variants("Apple", "1"),
+            name: "Apple_1",
+        );
+
+        def_prototype!(
+            STAGE_1,
+            base: base_stage(),
+            next_age: TREE_GROWTH_AGE,
+            next_stage: STAGE_2.get(),
+            model_variants: tree_variants("Apple", "2"),
+            name: "Apple_2",
+        );
+
+        def_prototype!(
+            STAGE_2,
+            base: base_stage(),
+            next_age: TREE_GROWTH_AGE,
+            next_stage: STAGE_3.get(),
+            model_variants: tree_variants("Apple", "3"),
+            name: "Apple_3",
+        );
+
+        def_prototype!(
+            STAGE_3,
+            base: base_stage(),
+            next_age: TREE_GROWTH_AGE,
+            next_stage: STAGE_4.get(),
+            model_variants: tree_variants("Apple", "4"),
+            name: "Apple_4",
+        );
+
+        def_prototype!(
+            STAGE_4,
+            base: base_stage(),
+            next_age: TREE_GROWTH_AGE,
+            next_stage: STAGE_5.get(),
+            model_variants: tree_variants("Apple", "5"),
+            name: "Apple_5",
+        );
+
+        def_prototype!(
+            STAGE_5,
+            base: base_stage(),
+            next_age: TREE_GROWTH_AGE,
+            next_stage: STAGE_6.get(),
+            model_variants: tree_variants("Apple", "6"),
+            name: "Apple_6",
+        );
+
+        def_prototype!(
+            STAGE_6,
+            base: base_stage(),
+            next_age: TREE_FRUIT_AGE,
+            next_stage: STAGE_5.get(),
+            pick_up_item_class: items::apple::FRUIT.get(),
+            pick_up_next_stage: STAGE_5.get(),
+            model_variants: tree_variants("Apple", "7"),
+            name: "Apple_7",
+        );
+    }
+
+    pub mod blue_spruce {
+        use super::*;
+
+        // spruces keep growing through the winter
+        const COMFORTABLE_TEMPERATURE: Vec2 = Vec2::new(-10.0, 20.0);
+
+        /// The components shared by every growth stage.
+        fn base_stage() -> Entity {
+            def_entity!(
+                is_large_crop: (),
+                crop_tags: tags(&["blue_spruce", "tree"]),
+                comfortable_temperature: COMFORTABLE_TEMPERATURE,
+                survivable_temperature: TREE_SURVIVABLE_TEMPERATURE,
+                large_crop_footprint: TREE_FOOTPRINT,
+            )
+        }
+
+        def_prototype!(
+            STAGE_0,
+            base: base_stage(),
+            next_age: TREE_GROWTH_AGE,
+            next_stage: STAGE_1.get(),
+            model_variants: tree_variants("Blue_Spruce", "1"),
+            name: "Blue_Spruce_1",
+        );
+
+        def_prototype!(
+            STAGE_1,
+            base: base_stage(),
+            next_age: TREE_GROWTH_AGE,
+            next_stage: STAGE_2.get(),
+            model_variants: tree_variants("Blue_Spruce", "2"),
+            name: "Blue_Spruce_2",
+        );
+
+        def_prototype!(
+            STAGE_2,
+            base: base_stage(),
+            next_age: TREE_GROWTH_AGE,
+            next_stage: STAGE_3.get(),
+            model_variants: tree_variants("Blue_Spruce", "3"),
+            name: "Blue_Spruce_3",
+        );
+
+        def_prototype!(
+            STAGE_3,
+            base: base_stage(),
+            next_age: TREE_GROWTH_AGE,
+            next_stage: STAGE_4.get(),
+            model_variants: tree_variants("Blue_Spruce", "4"),
+            name: "Blue_Spruce_4",
+        );
+
+        def_prototype!(
+            STAGE_4,
+            base: base_stage(),
+            next_age: TREE_GROWTH_AGE,
+            next_stage: STAGE_5.get(),
+            model_variants: tree_variants("Blue_Spruce", "5"),
+            name: "Blue_Spruce_5",
+        );
+
+        def_prototype!(
+            STAGE_5,
+            base: base_stage(),
+            next_age: TREE_FRUIT_AGE,
+            next_stage: STAGE_4.get(),
+            pick_up_item_class: items::blue_spruce::PINECONE.get(),
+            pick_up_next_stage: STAGE_4.get(),
+            model_variants: tree_variants("Blue_Spruce", "6"),
+            name: "Blue_Spruce_6",
+        );
+    }
+
+    pub mod cherry {
+        use super::*;
+
+        /// The components shared by every growth stage.
+        fn base_stage() -> Entity {
+            def_entity!(
+                is_large_crop: (),
+                crop_tags: tags(&["cherry", "tree"]),
+                comfortable_temperature: TREE_COMFORTABLE_TEMPERATURE,
+                survivable_temperature: TREE_SURVIVABLE_TEMPERATURE,
+                large_crop_footprint: TREE_FOOTPRINT,
+            )
+        }
+
+        def_prototype!(
+            STAGE_0,
+            base: base_stage(),
+            next_age: TREE_GROWTH_AGE,
+            next_stage: STAGE_1.get(),
+            model_variants: tree_variants("Cherry", "1"),
+            name: "Cherry_1",
+        );
+
+        def_prototype!(
+            STAGE_1,
+            base: base_stage(),
+            next_age: TREE_GROWTH_AGE,
+            next_stage: STAGE_2.get(),
+            model_variants: tree_variants("Cherry", "2"),
+            name: "Cherry_2",
+        );
+
+        def_prototype!(
+            STAGE_2,
+            base: base_stage(),
+            next_age: TREE_GROWTH_AGE,
+            next_stage: STAGE_3.get(),
+            model_variants: tree_variants("Cherry", "3"),
+            name: "Cherry_3",
+        );
+
+        def_prototype!(
+            STAGE_3,
+            base: base_stage(),
+            next_age: TREE_GROWTH_AGE,
+            next_stage: STAGE_4.get(),
+            model_variants: tree_variants("Cherry", "3.5"),
+            name: "Cherry_3.5",
+        );
+
+        def_prototype!(
+            STAGE_4,
+            base: base_stage(),
+            next_age: TREE_GROWTH_AGE,
+            next_stage: STAGE_5.get(),
+            model_variants: tree_variants("Cherry", "4"),
+            name: "Cherry_4",
+        );
+
+        def_prototype!(
+            STAGE_5,
+            base: base_stage(),
+            next_age: TREE_GROWTH_AGE,
+            next_stage: STAGE_6.get(),
+            model_variants: tree_variants("Cherry", "5"),
+            name: "Cherry_5",
+        );
+
+        def_prototype!(
+            STAGE_6,
+            base: base_stage(),
+            next_age: TREE_FRUIT_AGE,
+            next_stage: STAGE_5.get(),
+            pick_up_item_class: items::cherry::FRUIT.get(),
+            pick_up_next_stage: STAGE_5.get(),
+            model_variants: tree_variants("Cherry", "6"),
+            name: "Cherry_6",
+        );
+    }
+
+    pub mod peach {
+        use super::*;
+
+        /// The components shared by every growth stage.
+        fn base_stage() -> Entity {
+            def_entity!(
+                is_large_crop: (),
+                crop_tags: tags(&["peach", "tree"]),
+                comfortable_temperature: TREE_COMFORTABLE_TEMPERATURE,
+                survivable_temperature: TREE_SURVIVABLE_TEMPERATURE,
+                large_crop_footprint: TREE_FOOTPRINT,
+            )
+        }
+
+        def_prototype!(
+            STAGE_0,
+            base: base_stage(),
+            next_age: TREE_GROWTH_AGE,
+            next_stage: STAGE_1.get(),
+            model_variants: tree_variants("Peach", "1"),
+            name: "Peach_1",
+        );
+
+        def_prototype!(
+            STAGE_1,
+            base: base_stage(),
+            next_age: TREE_GROWTH_AGE,
+            next_stage: STAGE_2.get(),
+            model_variants: tree_variants("Peach", "2"),
+            name: "Peach_2",
+        );
+
+        def_prototype!(
+            STAGE_2,
+            base: base_stage(),
+            next_age: TREE_GROWTH_AGE,
+            next_stage: STAGE_3.get(),
+            model_variants: tree_variants("Peach", "3"),
+            name: "Peach_3",
+        );
+
+        def_prototype!(
+            STAGE_3,
+            base: base_stage(),
+            next_age: TREE_GROWTH_AGE,
+            next_stage: STAGE_4.get(),
+            model_variants: tree_variants("Peach", "4"),
+            name: "Peach_4",
+        );
+
+        def_prototype!(
+            STAGE_4,
+            base: base_stage(),
+            next_age: TREE_GROWTH_AGE,
+            next_stage: STAGE_5.get(),
+            model_variants: tree_variants("Peach", "5"),
+            name: "Peach_5",
+        );
+
+        def_prototype!(
+            STAGE_5,
+            base: base_stage(),
+            next_age: TREE_FRUIT_AGE,
+            next_stage: STAGE_4.get(),
+            pick_up_item_class: items::peach::FRUIT.get(),
+            pick_up_next_stage: STAGE_4.get(),
+            model_variants: tree_variants("Peach", "6"),
+            name: "Peach_6",
+        );
+    }
+
+    pub mod viola {
+        use super::*;
+
+        /// The components shared by every growth stage.
+        fn base_stage() -> Entity {
+            def_entity!(
+                is_small_crop: (),
+                is_cover_crop: (),
+                crop_tags: tags(&["viola", "flower"]),
+            )
+        }
+
+        def_prototype!(
+            STAGE_0,
+            base: base_stage(),
+            next_age: GROWTH_AGE,
+            next_stage: STAGE_1.get(),
+            // cover crops put nutrients back into the soil
+            fertility_use: -0.05_f32,
+            prefab: url("items/Flowers/Viola_cuttings_2.fbx"),
+            name: "Viola_0",
+        );
+
+        def_prototype!(
+            STAGE_1,
+            base: base_stage(),
+            seed: STAGE_0.get(),
+            seeding_interval: SEEDING_INTERVAL,
+            max_spread_density: 0.5_f32,
+            pick_up_item_class: items::viola::CUTTINGS.get(),
+            pick_up_next_stage: STAGE_0.get(),
+            prefab: url("items/Flowers/Viola_cuttings_1.fbx"),
+            name: "Viola_1",
+        );
+    }
+
+    pub mod ragwort {
+        use super::*;
+
+        def_prototype!(
+            STAGE_0,
+            is_small_crop: (),
+            crop_tags: tags(&["weed"]),
+            seed: STAGE_0.get(),
+            seeding_interval: SEEDING_INTERVAL,
+            max_spread_density: 0.75_f32,
+            pick_up_item_class: items::ragwort::SEEDS.get(),
+            prefab: url("items/Flowers/yellow ragwort seeds.fbx"),
+            name: "Ragwort",
         );
     }
 }
 
+pub mod storage {
+    use super::*;
+
+    def_prototype!(
+        CHEST,
+        is_chest: (),
+        chest_size: 16_u32,
+        name: "Chest",
+    );
+}
+
 pub mod items {
     use super::*;
 
-    pub mod debug {
+    lazy_static::lazy_static! {
+        /// Every item class, so that they can all be spawned up front.
+        pub static ref ALL: Vec<EntityId> = vec![
+            beans::POD.get(),
+            beans::SEED.get(),
+            carrots::CARROT.get(),
+            corn::EAR.get(),
+            corn::KERNELS.get(),
+            garlic::BULB.get(),
+            garlic::CLOVE.get(),
+            peppers::PEPPER.get(),
+            potatos::POTATO.get(),
+            sugarcane::STALK.get(),
+            tomatoes::FRUIT.get(),
+            tomatoes::SEEDS.get(),
+            wheat::GRAINS.get(),
+            apple::FRUIT.get(),
+            apple::SEEDS.get(),
+            blue_spruce::PINECONE.get(),
+            cherry::FRUIT.get(),
+            cherry::PIT.get(),
+            peach::FRUIT.get(),
+            peach::PIT.get(),
+            viola::CUTTINGS.get(),
+            ragwort::SEEDS.get(),
+            tools::HOE.get(),
+            tools::WATERING_CAN.get(),
+            storage::CHEST.get(),
+        ];
+    }
+
+    pub mod tools {
         use super::*;
 
-        use ambient_api::{core::rendering::components::color, prelude::vec4};
+        def_prototype!(
+            HOE,
+            is_item: (),
+            is_tool: (),
+            item_tags: tags(&["tool"]),
+            tills_soil: (),
+            max_durability: 200_u32,
+            name: "Hoe",
+        );
 
         def_prototype!(
-            BLUE,
-            color: vec4(0.0, 0.0, 1.0, 1.0),
+            WATERING_CAN,
+            is_item: (),
+            is_tool: (),
+            item_tags: tags(&["tool"]),
+            waters_soil: 0.5_f32,
+            name: "Watering Can",
         );
+    }
+
+    pub mod storage {
+        use super::*;
 
         def_prototype!(
-            YELLOW,
-            color: vec4(1.0, 1.0, 0.0, 1.0),
-            place_medium_crop: crops::corn::STAGE_6.get(),
+            CHEST,
+            is_item: (),
+            item_tags: tags(&["storage"]),
+            place_chest: crate::storage::CHEST.get(),
+            name: "Chest",
         );
+    }
+
+    pub mod beans {
+        use super::*;
 
         def_prototype!(
-            GREEN,
-            color: vec4(0.0, 1.0, 0.0, 1.0),
+            POD,
+            is_item: (),
+            item_tags: tags(&["produce", "beans"]),
+            prefab: url("items/Crops/Beans.fbx"),
+            name: "Beans",
+        );
+
+        def_prototype!(
+            SEED,
+            is_item: (),
+            item_tags: tags(&["seed", "beans"]),
+            place_medium_crop: crops::beans::STAGE_0.get(),
+            prefab: url("items/Crops/Bean_singlar.fbx"),
+            name: "Bean_singlar",
         );
     }
-}
 
-#[main]
-fn main() {
-    // let mut showcase = crops::SHOWCASE.to_owned();
-    let mut showcase = Vec::new();
-
-    let mut add_tree = |category: &str, stage_num, variant_num| {
-        for variant in 1..=variant_num {
-            let mut row = Vec::new();
-            for stage in 1..=stage_num {
-                let label = format!("{category}_{stage}_{variant}");
-                let path = format!("crops/large/{category}/{label}.fbx");
-
-                let e = def_entity!(
-                    is_class: (),
-                    is_medium_crop: (),
-                    prefab: url(&path),
-                    name: label,
-                )
-                .spawn();
+    pub mod carrots {
+        use super::*;
 
-                row.push(e);
-            }
+        def_prototype!(
+            CARROT,
+            is_item: (),
+            item_tags: tags(&["produce", "seed", "carrots"]),
+            place_medium_crop: crops::carrots::STAGE_0.get(),
+            prefab: url("items/Crops/carrot.fbx"),
+            name: "carrot",
+        );
+    }
 
-            showcase.push(row);
-        }
-    };
+    pub mod corn {
+        use super::*;
+
+        def_prototype!(
+            EAR,
+            is_item: (),
+            item_tags: tags(&["produce", "corn"]),
+            prefab: url("items/Crops/maize ear.fbx"),
+            name: "maize ear",
+        );
+
+        def_prototype!(
+            KERNELS,
+            is_item: (),
+            item_tags: tags(&["seed", "corn"]),
+            place_medium_crop: crops::corn::STAGE_0.get(),
+            prefab: url("items/Crops/maize kernels.fbx"),
+            name: "maize kernels",
+        );
+    }
+
+    pub mod garlic {
+        use super::*;
+
+        def_prototype!(
+            BULB,
+            is_item: (),
+            item_tags: tags(&["produce", "garlic"]),
+            prefab: url("items/Crops/garlic bulb_1.fbx"),
+            name: "garlic bulb",
+        );
+
+        def_prototype!(
+            CLOVE,
+            is_item: (),
+            item_tags: tags(&["seed", "garlic"]),
+            place_medium_crop: crops::garlic::STAGE_0.get(),
+            prefab: url("items/Crops/garlic clove_1.fbx"),
+            name: "garlic clove",
+        );
+    }
+
+    pub mod peppers {
+        use super::*;
+
+        def_prototype!(
+            PEPPER,
+            is_item: (),
+            item_tags: tags(&["produce", "seed", "peppers"]),
+            place_medium_crop: crops::peppers::STAGE_0.get(),
+            prefab: url("items/Crops/pepper.fbx"),
+            name: "pepper",
+        );
+    }
+
+    pub mod potatos {
+        use super::*;
+
+        def_prototype!(
+            POTATO,
+            is_item: (),
+            item_tags: tags(&["produce", "seed", "potatos"]),
+            place_medium_crop: crops::potatos::STAGE_0.get(),
+            prefab: url("items/Crops/Potato.fbx"),
+            name: "Potato",
+        );
+    }
+
+    pub mod sugarcane {
+        use super::*;
 
-    add_tree("Apple", 7, 4);
-    add_tree("Blue_Spruce", 6, 4);
-    add_tree("Cherry", 6, 4);
-    add_tree("Peach", 6, 4);
+        def_prototype!(
+            STALK,
+            is_item: (),
+            item_tags: tags(&["produce", "seed", "sugarcane"]),
+            place_medium_crop: crops::sugarcane::STAGE_0.get(),
+            prefab: url("items/Crops/sugarcane stalk.fbx"),
+            name: "sugarcane stalk",
+        );
+    }
+
+    pub mod tomatoes {
+        use super::*;
 
+        def_prototype!(
+            FRUIT,
+            is_item: (),
+            item_tags: tags(&["produce", "tomatoes"]),
+            prefab: url("items/Crops/tomato fruit.fbx"),
+            name: "tomato fruit",
+        );
+
+        def_prototype!(
+            SEEDS,
+            is_item: (),
+            item_tags: tags(&["seed", "tomatoes"]),
+            place_medium_crop: crops::tomatoes::STAGE_0.get(),
+            prefab: url("items/Crops/tomato seeds.fbx"),
+            name: "tomato seeds",
+        );
+    }
+
+    pub mod wheat {
+        use super::*;
+
+        def_prototype!(
+            GRAINS,
+            is_item: (),
+            item_tags: tags(&["produce", "seed", "wheat"]),
+            place_medium_crop: crops::wheat::STAGE_0.get(),
+            prefab: url("items/Crops/wheat_Grains.fbx"),
+            name: "wheat grains",
+        );
+    }
+
+    pub mod apple {
+        use super::*;
+
+        def_prototype!(
+            FRUIT,
+            is_item: (),
+            item_tags: tags(&["produce", "fruit", "apple"]),
+            prefab: url("items/Trees/apple_fruit_1.fbx"),
+            name: "apple fruit",
+        );
+
+        def_prototype!(
+            SEEDS,
+            is_item: (),
+            item_tags: tags(&["seed", "apple"]),
+            place_large_crop: crops::apple::STAGE_0.get(),
+            prefab: url("items/Trees/apple_seeds.fbx"),
+            name: "apple seeds",
+        );
+    }
+
+    pub mod blue_spruce {
+        use super::*;
+
+        def_prototype!(
+            PINECONE,
+            is_item: (),
+            item_tags: tags(&["seed", "blue_spruce"]),
+            place_large_crop: crops::blue_spruce::STAGE_0.get(),
+            prefab: url("items/Trees/Pinecone.fbx"),
+            name: "pinecone",
+        );
+    }
+
+    pub mod cherry {
+        use super::*;
+
+        def_prototype!(
+            FRUIT,
+            is_item: (),
+            item_tags: tags(&["produce", "fruit", "cherry"]),
+            prefab: url("items/Trees/Cherry_fruit_1.fbx"),
+            name: "cherry fruit",
+        );
+
+        def_prototype!(
+            PIT,
+            is_item: (),
+            item_tags: tags(&["seed", "cherry"]),
+            place_large_crop: crops::cherry::STAGE_0.get(),
+            prefab: url("items/Trees/cherry pit.fbx"),
+            name: "cherry pit",
+        );
+    }
+
+    pub mod peach {
+        use super::*;
+
+        def_prototype!(
+            FRUIT,
+            is_item: (),
+            item_tags: tags(&["produce", "fruit", "peach"]),
+            prefab: url("items/Trees/peach_fruit.fbx"),
+            name: "peach fruit",
+        );
+
+        def_prototype!(
+            PIT,
+            is_item: (),
+            item_tags: tags(&["seed", "peach"]),
+            place_large_crop: crops::peach::STAGE_0.get(),
+            prefab: url("items/Trees/peach_pit.fbx"),
+            name: "peach pit",
+        );
+    }
+
+    pub mod viola {
+        use super::*;
+
+        def_prototype!(
+            CUTTINGS,
+            is_item: (),
+            item_tags: tags(&["seed", "viola"]),
+            place_small_crop: crops::viola::STAGE_0.get(),
+            prefab: url("items/Flowers/Viola_cuttings_1.fbx"),
+            name: "viola cuttings",
+        );
+    }
+
+    pub mod ragwort {
+        use super::*;
+
+        def_prototype!(
+            SEEDS,
+            is_item: (),
+            item_tags: tags(&["seed", "weed", "ragwort"]),
+            place_small_crop: crops::ragwort::STAGE_0.get(),
+            prefab: url("items/Flowers/yellow ragwort seeds.fbx"),
+            name: "ragwort seeds",
+        );
+    }
+}
+
+/// Plants an orchard with one row of every tree's growth stages.
+fn plant_orchard() {
     let row_spacing = 4;
     let col_spacing = 4;
 
-    for (y, row) in showcase.iter().enumerate() {
+    for (y, row) in crops::TREE_SHOWCASE.iter().enumerate() {
         for (x, class) in row.iter().enumerate() {
             Entity::new()
                 .with(class_ref(), *class)
@@ -767,14 +1831,78 @@ fn main() {
                 .spawn();
         }
     }
+}
+
+/// Plants a starter garden with one row of every crop's growth stages.
+fn plant_garden() {
+    for (y, row) in crops::SHOWCASE.iter().enumerate() {
+        for (x, class) in row.iter().enumerate() {
+            Entity::new()
+                .with(class_ref(), *class)
+                .with(coords(), ivec2(-2 - x as i32 * 2, y as i32 * 2))
+                .spawn();
+        }
+
+        // with a cover crop underneath the start of each row
+        Entity::new()
+            .with(class_ref(), crops::viola::STAGE_1.get())
+            .with(coords(), ivec2(-2, y as i32 * 2))
+            .spawn();
+    }
+
+    // and some weeds that will spread into it if left alone
+    for xy in [ivec2(-3, -3), ivec2(-12, -3)] {
+        Entity::new()
+            .with(class_ref(), crops::ragwort::STAGE_0.get())
+            .with(coords(), xy)
+            .spawn();
+    }
+}
+
+#[main]
+fn main() {
+    // spawn every crop and item class up front so that saved crops and held
+    // items can be restored, and so that content files can refer to them
+    lazy_static::initialize(&crops::SHOWCASE);
+    lazy_static::initialize(&crops::TREE_SHOWCASE);
+    lazy_static::initialize(&items::ALL);
+
+    run_async(async move {
+        // saved worlds already have their orchards and gardens
+        let new_world = entity::wait_for_component(entity::resources(), is_new_world())
+            .await
+            .unwrap_or(false);
+
+        if new_world {
+            plant_orchard();
+            plant_garden();
+        }
+    });
+
+    // give new players the tools that they need to start farming. returning
+    // players have their saved items restored instead.
+    spawn_query((left_hand_ref(), right_hand_ref()))
+        .requires((is_player(), is_new_player()))
+        .bind(move |entities| {
+            for (e, (left, right)) in entities {
+                entity::remove_component(e, is_new_player());
+
+                let tools = [
+                    (left, items::tools::HOE.get()),
+                    (right, items::tools::WATERING_CAN.get()),
+                ];
+
+                for (hand, tool) in tools {
+                    if entity::has_component(hand, held_ref()) {
+                        continue;
+                    }
+
+                    let uses = entity::get_component(tool, max_durability()).unwrap_or(0);
+                    let instance = spawn_instance(class(), quantity(), durability(), tool, 1, uses);
+                    entity::add_component(hand, held_ref(), instance);
+                }
+            }
+        });
 
-    use packages::crafting::components::*;
-    def_entity!(
-        is_recipe: (),
-        primary_ingredient: items::debug::YELLOW.get(),
-        secondary_ingredient: items::debug::BLUE.get(),
-        primary_yield: items::debug::GREEN.get(),
-        secondary_yield: EntityId::null(),
-    )
-    .spawn();
+    LoadContent::new(url("content.toml")).send_local_broadcast(false);
 }